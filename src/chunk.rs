@@ -0,0 +1,407 @@
+use crate::ignore::PathPattern;
+use crate::loads::to_python;
+use crate::rules::{Rule, Rules};
+use crate::{Flavor, RawCompound, sorted_entries};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList, PyTuple};
+use std::collections::{BTreeMap, HashMap};
+
+/// The containers of chunk sections whose entries are compared instead of their encoding
+#[derive(Clone, Copy)]
+pub(crate) enum Container {
+    BlockStates,
+    Biomes,
+    /// A whole section before 1.18, blocks are stored in `Palette` and `BlockStates`,
+    /// or `Blocks`, `Data` and `Add` before 1.13
+    Section,
+}
+
+impl Container {
+    const PATTERNS: [(&str, Container); 3] = [
+        ("sections[*].block_states", Container::BlockStates),
+        ("sections[*].biomes", Container::Biomes),
+        ("Level.Sections[*]", Container::Section),
+    ];
+
+    fn len(self) -> usize {
+        match self {
+            Container::BlockStates | Container::Section => 16 * 16 * 16,
+            Container::Biomes => 4 * 4 * 4,
+        }
+    }
+
+    /// Bits per entry the game uses to store a palette of this size
+    fn bits(self, palette_len: usize) -> u32 {
+        let bits = usize::BITS - (palette_len - 1).leading_zeros();
+        match self {
+            Container::BlockStates if bits > 0 => bits.max(4),
+            Container::Section => bits.max(4),
+            _ => bits,
+        }
+    }
+}
+
+const PALETTED_KEYS: [&[u8]; 2] = [b"palette", b"data"];
+const SECTION_KEYS: [&[u8]; 2] = [b"Palette", b"BlockStates"];
+const NUMERIC_KEYS: [&[u8]; 3] = [b"Blocks", b"Data", b"Add"];
+
+/// The first DataVersion with entries that do not span multiple longs (20w17a)
+const NON_SPANNING: i32 = 2529;
+
+/// Compare the blocks and biomes of chunk sections by their decoded entries
+pub(crate) fn push_rules(rules: &mut Rules) {
+    for (pattern, container) in Container::PATTERNS {
+        rules.push(
+            PathPattern::parse(pattern).unwrap(),
+            Rule::Paletted(container),
+        );
+    }
+}
+
+/// Key of the version a chunk or other root tag was saved with
+pub(crate) const DATA_VERSION: &[u8] = b"DataVersion";
+
+/// The `DataVersion` of a parsed chunk or other root tag
+pub(crate) fn data_version(root: &RawCompound) -> Option<i32> {
+    let RawCompound::Map(_, root) = root else {
+        return None;
+    };
+    match root.get(DATA_VERSION)? {
+        RawCompound::Mem(3, value) => Some(i32::from_be_bytes((*value).try_into().ok()?)),
+        _ => None,
+    }
+}
+
+/// What the indices of a decoded container refer to
+#[derive(Clone, Copy)]
+pub(crate) enum Palette<'t, 'a> {
+    Entries(&'t [RawCompound<'a>]),
+    /// Blocks before 1.13, each index is `id << 4 | data`
+    Numeric,
+}
+
+/// A decoded container with an index into its palette for every entry
+pub(crate) struct Decoded<'t, 'a> {
+    pub(crate) palette: Palette<'t, 'a>,
+    pub(crate) indices: Vec<u16>,
+    /// The key of the palette within the container
+    pub(crate) palette_key: &'static [u8],
+    /// The entries of the container that do not hold blocks or biomes, sorted by key
+    pub(crate) rest: Vec<(&'a [u8], &'t RawCompound<'a>)>,
+}
+
+impl<'t, 'a> Decoded<'t, 'a> {
+    /// Whether entry `l` of this container is the same as entry `r` of `other`
+    pub(crate) fn entries_eq(
+        &self,
+        other: &Self,
+        l: u16,
+        r: u16,
+        eq: impl FnOnce(&RawCompound<'a>, &RawCompound<'a>) -> bool,
+    ) -> bool {
+        match (self.palette, other.palette) {
+            (Palette::Entries(left), Palette::Entries(right)) => {
+                eq(&left[usize::from(l)], &right[usize::from(r)])
+            }
+            (Palette::Numeric, Palette::Numeric) => l == r,
+            _ => false,
+        }
+    }
+}
+
+/// Decode a container, `None` if it is malformed or holds no entries.
+/// The `data_version` of the chunk decides how sections before 1.18 are packed.
+pub(crate) fn decode<'t, 'a>(
+    container: &'t RawCompound<'a>,
+    kind: Container,
+    data_version: Option<i32>,
+) -> Option<Decoded<'t, 'a>> {
+    let RawCompound::Map(_, map) = container else {
+        return None;
+    };
+    let (keys, spanning) = match kind {
+        Container::BlockStates | Container::Biomes => (&PALETTED_KEYS, Some(false)),
+        Container::Section if map.contains_key(&b"Blocks"[..]) => return decode_numeric(map),
+        Container::Section => (
+            &SECTION_KEYS,
+            data_version.map(|version| version < NON_SPANNING),
+        ),
+    };
+    let [palette_key, data_key] = *keys;
+    let Some(RawCompound::List(_, palette)) = map.get(palette_key) else {
+        return None;
+    };
+    if palette.is_empty() || palette.len() > usize::from(u16::MAX) {
+        return None;
+    }
+    let bits = kind.bits(palette.len());
+    let indices = match bits {
+        0 => vec![0; kind.len()],
+        _ => {
+            let Some(RawCompound::Mem(12, data)) = map.get(data_key) else {
+                return None;
+            };
+            unpack(data, bits, kind.len(), spanning)?
+        }
+    };
+    indices
+        .iter()
+        .all(|&index| usize::from(index) < palette.len())
+        .then(|| Decoded {
+            palette: Palette::Entries(palette),
+            indices,
+            palette_key,
+            rest: sorted_entries(map, keys),
+        })
+}
+
+/// Unpack `len` entries of `bits` bits from a long array.
+/// If `spanning` is unknown, it is derived from the length of `data`.
+fn unpack(data: &[u8], bits: u32, len: usize, spanning: Option<bool>) -> Option<Vec<u16>> {
+    let longs: Vec<u64> = data
+        .chunks_exact(8)
+        .map(|long| u64::from_be_bytes(long.try_into().unwrap()))
+        .collect();
+    let per_long = (u64::BITS / bits) as usize;
+    let spanning_len = (len * bits as usize).div_ceil(64);
+    let spanning = match spanning {
+        Some(spanning) => spanning,
+        None if longs.len() == spanning_len => true,
+        None => false,
+    };
+    let expected = match spanning {
+        true => spanning_len,
+        false => len.div_ceil(per_long),
+    };
+    if longs.len() != expected {
+        return None;
+    }
+    let mask = (1 << bits) - 1;
+    let entries = (0..len).map(|index| {
+        if !spanning {
+            let shift = (index % per_long) as u32 * bits;
+            return (longs[index / per_long] >> shift & mask) as u16;
+        }
+        let offset = index * bits as usize;
+        let (long, shift) = (offset / 64, (offset % 64) as u32);
+        let mut value = longs[long] >> shift;
+        if shift + bits > 64 {
+            value |= longs[long + 1] << (64 - shift);
+        }
+        (value & mask) as u16
+    });
+    Some(entries.collect())
+}
+
+/// Decode the `Blocks`, `Data` and `Add` arrays of a section before 1.13
+fn decode_numeric<'t, 'a>(map: &'t HashMap<&'a [u8], RawCompound<'a>>) -> Option<Decoded<'t, 'a>> {
+    let nibbles = |key: &[u8]| match map.get(key) {
+        Some(RawCompound::Mem(7, nibbles)) if nibbles.len() == 2048 => Some(Some(*nibbles)),
+        Some(_) => None,
+        None => Some(None),
+    };
+    let Some(RawCompound::Mem(7, blocks)) = map.get(&b"Blocks"[..]) else {
+        return None;
+    };
+    let (data, add) = (nibbles(b"Data")?, nibbles(b"Add")?);
+    if blocks.len() != 4096 {
+        return None;
+    }
+    let nibble = |array: Option<&[u8]>, index: usize| {
+        array.map_or(0, |array| {
+            u16::from(array[index / 2] >> (index % 2 * 4) & 15)
+        })
+    };
+    let indices = (0..4096)
+        .map(|index| {
+            let id = nibble(add, index) << 8 | u16::from(blocks[index]);
+            id << 4 | nibble(data, index)
+        })
+        .collect();
+    Some(Decoded {
+        palette: Palette::Numeric,
+        indices,
+        palette_key: b"",
+        rest: sorted_entries(map, &NUMERIC_KEYS),
+    })
+}
+
+/// The decoded blocks of all sections of a chunk, by section Y.
+/// Sections without blocks are skipped.
+pub(crate) fn block_sections<'t, 'a>(
+    chunk: &'t RawCompound<'a>,
+) -> PyResult<BTreeMap<i8, Decoded<'t, 'a>>> {
+    let mut res = BTreeMap::new();
+    let data_version = data_version(chunk);
+    let RawCompound::Map(_, root) = chunk else {
+        return Ok(res);
+    };
+    let (sections, container) = match (root.get(&b"sections"[..]), root.get(&b"Level"[..])) {
+        (Some(RawCompound::List(_, sections)), _) => (sections, Container::BlockStates),
+        (_, Some(RawCompound::Map(_, level))) => match level.get(&b"Sections"[..]) {
+            Some(RawCompound::List(_, sections)) => (sections, Container::Section),
+            _ => return Ok(res),
+        },
+        _ => return Ok(res),
+    };
+    for section in sections {
+        let RawCompound::Map(_, map) = section else {
+            continue;
+        };
+        let Some(RawCompound::Mem(1, [y])) = map.get(&b"Y"[..]) else {
+            continue;
+        };
+        let blocks = match container {
+            Container::Section
+                if map.contains_key(&b"Palette"[..]) || map.contains_key(&b"Blocks"[..]) =>
+            {
+                section
+            }
+            Container::BlockStates if let Some(block_states) = map.get(&b"block_states"[..]) => {
+                block_states
+            }
+            _ => continue,
+        };
+        let y = *y as i8;
+        let decoded = decode(blocks, container, data_version)
+            .ok_or_else(|| PyValueError::new_err(format!("Malformed blocks in section {y}")))?;
+        res.insert(y, decoded);
+    }
+    Ok(res)
+}
+
+/// The blocks that differ within one section
+pub(crate) struct SectionDiff<'t, 'a> {
+    y: i8,
+    /// The palettes of both sides, `None` if the section is missing on that side
+    left: Option<Palette<'t, 'a>>,
+    right: Option<Palette<'t, 'a>>,
+    /// Block index and the palette index on both sides
+    blocks: Vec<(usize, Option<u16>, Option<u16>)>,
+}
+
+/// Compare the blocks of all sections, skipping sections without changes
+pub(crate) fn diff_sections<'t, 'a>(
+    left: &BTreeMap<i8, Decoded<'t, 'a>>,
+    right: &BTreeMap<i8, Decoded<'t, 'a>>,
+) -> Vec<SectionDiff<'t, 'a>> {
+    let mut ys: Vec<i8> = left.keys().chain(right.keys()).copied().collect();
+    ys.sort_unstable();
+    ys.dedup();
+    let mut res = Vec::new();
+    for y in ys {
+        let (l, r) = (left.get(&y), right.get(&y));
+        let blocks: Vec<_> = match (l, r) {
+            (Some(l), Some(r)) => {
+                let mut checked = HashMap::new();
+                l.indices
+                    .iter()
+                    .zip(&r.indices)
+                    .enumerate()
+                    .filter(|&(_, (&li, &ri))| {
+                        !*checked
+                            .entry((li, ri))
+                            .or_insert_with(|| l.entries_eq(r, li, ri, |l, r| l == r))
+                    })
+                    .map(|(index, (&li, &ri))| (index, Some(li), Some(ri)))
+                    .collect()
+            }
+            (Some(l), None) => l
+                .indices
+                .iter()
+                .enumerate()
+                .map(|(index, &li)| (index, Some(li), None))
+                .collect(),
+            (None, Some(r)) => r
+                .indices
+                .iter()
+                .enumerate()
+                .map(|(index, &ri)| (index, None, Some(ri)))
+                .collect(),
+            (None, None) => unreachable!(),
+        };
+        if !blocks.is_empty() {
+            res.push(SectionDiff {
+                y,
+                left: l.map(|l| l.palette),
+                right: r.map(|r| r.palette),
+                blocks,
+            });
+        }
+    }
+    res
+}
+
+/// Convert the differences into a dict from section Y to lists of `(x, y, z, old_state, new_state)`
+pub(crate) fn sections_to_python<'py>(
+    py: Python<'py>,
+    diffs: Vec<SectionDiff>,
+    left_data: &[u8],
+    right_data: &[u8],
+) -> PyResult<Bound<'py, PyDict>> {
+    let res = PyDict::new(py);
+    for diff in diffs {
+        // palette entries are converted once, most of them are used by many blocks
+        let mut left_states = StateCache::new(diff.left, left_data);
+        let mut right_states = StateCache::new(diff.right, right_data);
+        let blocks = PyList::empty(py);
+        for (index, left, right) in diff.blocks {
+            let (x, y, z) = (index & 15, index >> 8, (index >> 4) & 15);
+            let old_state = left_states.get(py, left)?;
+            let new_state = right_states.get(py, right)?;
+            blocks.append(PyTuple::new(
+                py,
+                [
+                    x.into_pyobject(py)?.into_any(),
+                    y.into_pyobject(py)?.into_any(),
+                    z.into_pyobject(py)?.into_any(),
+                    old_state,
+                    new_state,
+                ],
+            )?)?;
+        }
+        res.set_item(diff.y, blocks)?;
+    }
+    Ok(res)
+}
+
+struct StateCache<'t, 'a, 'd, 'py> {
+    palette: Option<Palette<'t, 'a>>,
+    data: &'d [u8],
+    states: Vec<Option<Bound<'py, PyAny>>>,
+}
+
+impl<'t, 'a, 'd, 'py> StateCache<'t, 'a, 'd, 'py> {
+    fn new(palette: Option<Palette<'t, 'a>>, data: &'d [u8]) -> Self {
+        let len = match palette {
+            Some(Palette::Entries(entries)) => entries.len(),
+            _ => 0,
+        };
+        StateCache {
+            palette,
+            data,
+            states: vec![None; len],
+        }
+    }
+
+    fn get(&mut self, py: Python<'py>, index: Option<u16>) -> PyResult<Bound<'py, PyAny>> {
+        let (Some(palette), Some(index)) = (self.palette, index) else {
+            return Ok(py.None().into_bound(py));
+        };
+        let Palette::Entries(palette) = palette else {
+            return Ok((index >> 4, index & 15).into_pyobject(py)?.into_any());
+        };
+        let index = usize::from(index);
+        if self.states[index].is_none() {
+            self.states[index] = Some(to_python(
+                py,
+                &palette[index],
+                self.data,
+                Flavor::Java,
+                false,
+            )?);
+        }
+        Ok(self.states[index].clone().unwrap())
+    }
+}
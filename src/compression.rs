@@ -0,0 +1,70 @@
+use flate2::read::{GzDecoder, ZlibDecoder};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::pybacked::PyBackedStr;
+use std::borrow::Cow;
+use std::io::Read;
+
+#[derive(Clone, Copy)]
+pub(crate) enum Compression {
+    Auto,
+    None,
+    Gzip,
+    Zlib,
+}
+
+impl FromPyObject<'_, '_> for Compression {
+    type Error = PyErr;
+
+    fn extract(obj: Borrowed<'_, '_, PyAny>) -> PyResult<Self> {
+        match &*obj.extract::<PyBackedStr>()? {
+            "auto" => Ok(Compression::Auto),
+            "none" => Ok(Compression::None),
+            "gzip" => Ok(Compression::Gzip),
+            "zlib" => Ok(Compression::Zlib),
+            other => Err(PyValueError::new_err(format!(
+                "Unknown compression: {other:?}"
+            ))),
+        }
+    }
+}
+
+impl Compression {
+    /// Guess the compression from the header. NBT never starts with 0x1f or 0x78,
+    /// other zlib headers are only recognized if their check bits are valid.
+    /// A TAG_String root (0x08) can still pass as zlib, see [`decompress`].
+    fn detect(data: &[u8]) -> Self {
+        match data {
+            [0x1f, 0x8b, ..] => Compression::Gzip,
+            [cmf, flg, ..]
+                if cmf & 0x0f == 8
+                    && cmf >> 4 <= 7
+                    && u16::from_be_bytes([*cmf, *flg]) % 31 == 0 =>
+            {
+                Compression::Zlib
+            }
+            _ => Compression::None,
+        }
+    }
+}
+
+pub(crate) fn decompress(data: &[u8], compression: Compression) -> PyResult<Cow<'_, [u8]>> {
+    let mut res = Vec::new();
+    let (name, result) = match compression {
+        Compression::Auto => {
+            return match Compression::detect(data) {
+                // keep a TAG_String root that only looks like zlib data
+                Compression::Zlib if data[0] == 8 => Ok(decompress(data, Compression::Zlib)
+                    .ok()
+                    .filter(|res| res.first().is_some_and(|tag_id| *tag_id <= 12))
+                    .unwrap_or(Cow::Borrowed(data))),
+                compression => decompress(data, compression),
+            };
+        }
+        Compression::None => return Ok(Cow::Borrowed(data)),
+        Compression::Gzip => ("gzip", GzDecoder::new(data).read_to_end(&mut res)),
+        Compression::Zlib => ("zlib", ZlibDecoder::new(data).read_to_end(&mut res)),
+    };
+    result.map_err(|e| PyValueError::new_err(format!("Invalid {name} data: {e}")))?;
+    Ok(Cow::Owned(res))
+}
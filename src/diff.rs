@@ -0,0 +1,245 @@
+use crate::equality::Equality;
+use crate::ignore::Matcher;
+use crate::path::{PathSegment, format_path};
+use crate::rules::Rule;
+use crate::unordered::{elements, pair_up};
+use crate::{RawCompound, sorted_entries};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+#[derive(Clone, Copy)]
+pub(crate) enum DiffKind {
+    Added,
+    Removed,
+    Changed,
+    TypeChanged,
+}
+
+impl DiffKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            DiffKind::Added => "added",
+            DiffKind::Removed => "removed",
+            DiffKind::Changed => "changed",
+            DiffKind::TypeChanged => "type-changed",
+        }
+    }
+}
+
+/// A difference that can be created without holding the GIL.
+pub(crate) struct RawDifference {
+    path: String,
+    kind: DiffKind,
+    left: Option<(u8, Vec<u8>)>,
+    right: Option<(u8, Vec<u8>)>,
+}
+
+impl RawDifference {
+    pub(crate) fn into_python(self, py: Python<'_>) -> Difference {
+        let tag_id = |side: &Option<(u8, Vec<u8>)>| side.as_ref().map(|(tag_id, _)| *tag_id);
+        let payload = |side: Option<(u8, Vec<u8>)>| {
+            side.map(|(_, payload)| PyBytes::new(py, &payload).unbind())
+        };
+        Difference {
+            path: self.path,
+            kind: self.kind.as_str(),
+            left_tag: tag_id(&self.left),
+            left: payload(self.left),
+            right_tag: tag_id(&self.right),
+            right: payload(self.right),
+        }
+    }
+}
+
+/// A single difference between two NBT trees.
+///
+/// `left` and `right` hold the raw payload of the value on either side (`None` if it is missing).
+/// Length prefixes and list headers are stripped, so the tag id is needed to interpret them.
+#[pyclass(frozen, get_all, module = "nbtcompare._core")]
+pub(crate) struct Difference {
+    path: String,
+    kind: &'static str,
+    left_tag: Option<u8>,
+    left: Option<Py<PyBytes>>,
+    right_tag: Option<u8>,
+    right: Option<Py<PyBytes>>,
+}
+
+#[pymethods]
+impl Difference {
+    fn __repr__(&self) -> String {
+        format!("<Difference {} {:?}>", self.kind, self.path)
+    }
+}
+
+pub(crate) fn diff_raw<'a>(
+    left: &RawCompound<'a>,
+    right: &RawCompound<'a>,
+    equality: Equality,
+) -> Vec<RawDifference> {
+    let mut differ = Differ {
+        path: Vec::new(),
+        diffs: Vec::new(),
+        equality,
+    };
+    differ.diff(left, right, &equality.rules.matcher());
+    differ.diffs
+}
+
+struct Differ<'a, 'o> {
+    path: Vec<PathSegment<'a>>,
+    diffs: Vec<RawDifference>,
+    equality: Equality<'o>,
+}
+
+impl<'a> Differ<'a, '_> {
+    fn diff(&mut self, left: &RawCompound<'a>, right: &RawCompound<'a>, matcher: &Matcher) {
+        match self.equality.rules.rule(matcher) {
+            Some(Rule::Unordered(key)) if same_element_type(left, right) => {
+                let (mut left_unpacked, mut right_unpacked) = (Vec::new(), Vec::new());
+                if let (Some(l), Some(r)) = (
+                    elements(left, &mut left_unpacked),
+                    elements(right, &mut right_unpacked),
+                ) {
+                    return self.diff_unordered(l, r, key.as_deref(), matcher);
+                }
+            }
+            Some(&Rule::Paletted(container)) => {
+                if let Some((l, r)) = self.equality.decode_pair(left, right, container) {
+                    // palette order and packing are not worth reporting, only whether entries changed
+                    if !self.equality.entries_eq(&l, &r, matcher) {
+                        return self.push(DiffKind::Changed, Some(left), Some(right));
+                    }
+                    return self.diff_entries(l.rest, r.rest, matcher);
+                }
+            }
+            _ => {}
+        }
+        match (left, right) {
+            (RawCompound::Map(_, l), RawCompound::Map(_, r)) => {
+                self.diff_entries(sorted_entries(l, &[]), sorted_entries(r, &[]), matcher);
+            }
+            (RawCompound::List(_, l), RawCompound::List(_, r))
+                if same_element_type(left, right) =>
+            {
+                for index in 0..l.len().max(r.len()) {
+                    self.path.push(PathSegment::Index(index));
+                    let child = matcher.descend(PathSegment::Index(index));
+                    self.diff_optional(l.get(index), r.get(index), &child);
+                    self.path.pop();
+                }
+            }
+            _ if self.equality.trees_eq(left, right, matcher) => {}
+            _ if left.tag_id() == right.tag_id() && same_element_type(left, right) => {
+                self.push(DiffKind::Changed, Some(left), Some(right))
+            }
+            _ => self.push(DiffKind::TypeChanged, Some(left), Some(right)),
+        }
+    }
+
+    /// Elements are reported at their index on the left, added elements at their index on the right
+    fn diff_unordered(
+        &mut self,
+        left: &[RawCompound<'a>],
+        right: &[RawCompound<'a>],
+        key: Option<&[u8]>,
+        matcher: &Matcher,
+    ) {
+        let transitive = self.equality.floats.is_transitive();
+        let pairing = pair_up(left, right, key, transitive, |left_index, right_index| {
+            let child = matcher.descend(PathSegment::Index(left_index));
+            self.equality
+                .trees_eq(&left[left_index], &right[right_index], &child)
+        });
+        let mut reported: Vec<_> = pairing
+            .changed
+            .into_iter()
+            .map(|(left_index, right_index)| (left_index, Some(right_index)))
+            .chain(pairing.removed.into_iter().map(|index| (index, None)))
+            .collect();
+        reported.sort_unstable();
+        for (index, right_index) in reported {
+            self.path.push(PathSegment::Index(index));
+            let child = matcher.descend(PathSegment::Index(index));
+            self.diff_optional(Some(&left[index]), right_index.map(|i| &right[i]), &child);
+            self.path.pop();
+        }
+        for index in pairing.added {
+            self.path.push(PathSegment::Index(index));
+            self.push(DiffKind::Added, None, Some(&right[index]));
+            self.path.pop();
+        }
+    }
+
+    /// Diff compound entries that are sorted by key
+    fn diff_entries(
+        &mut self,
+        left: Vec<(&'a [u8], &RawCompound<'a>)>,
+        right: Vec<(&'a [u8], &RawCompound<'a>)>,
+        matcher: &Matcher,
+    ) {
+        let (mut left, mut right) = (left.into_iter().peekable(), right.into_iter().peekable());
+        loop {
+            let (key, l, r) = match (left.peek(), right.peek()) {
+                (Some((l_key, _)), Some((r_key, _))) if l_key == r_key => {
+                    let ((key, l), (_, r)) = (left.next().unwrap(), right.next().unwrap());
+                    (key, Some(l), Some(r))
+                }
+                (Some((l_key, _)), Some((r_key, _))) if l_key < r_key => {
+                    let (key, l) = left.next().unwrap();
+                    (key, Some(l), None)
+                }
+                (Some(_), None) => {
+                    let (key, l) = left.next().unwrap();
+                    (key, Some(l), None)
+                }
+                (_, Some(_)) => {
+                    let (key, r) = right.next().unwrap();
+                    (key, None, Some(r))
+                }
+                (None, None) => return,
+            };
+            self.path.push(PathSegment::Key(key));
+            self.diff_optional(l, r, &matcher.descend(PathSegment::Key(key)));
+            self.path.pop();
+        }
+    }
+
+    fn diff_optional(
+        &mut self,
+        left: Option<&RawCompound<'a>>,
+        right: Option<&RawCompound<'a>>,
+        matcher: &Matcher,
+    ) {
+        match (left, right) {
+            (Some(left), Some(right)) => self.diff(left, right, matcher),
+            (Some(_), None) => self.push(DiffKind::Removed, left, None),
+            (None, Some(_)) => self.push(DiffKind::Added, None, right),
+            (None, None) => unreachable!(),
+        }
+    }
+
+    fn push(
+        &mut self,
+        kind: DiffKind,
+        left: Option<&RawCompound<'a>>,
+        right: Option<&RawCompound<'a>>,
+    ) {
+        let side =
+            |side: Option<&RawCompound<'a>>| side.map(|tag| (tag.tag_id(), tag.payload().to_vec()));
+        self.diffs.push(RawDifference {
+            path: format_path(&self.path),
+            kind,
+            left: side(left),
+            right: side(right),
+        });
+    }
+}
+
+/// Lists only differ in type if both have elements and their element types differ
+fn same_element_type(left: &RawCompound, right: &RawCompound) -> bool {
+    match (left.element_id(), right.element_id()) {
+        (Some(l), Some(r)) => l == r,
+        _ => true,
+    }
+}
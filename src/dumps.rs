@@ -0,0 +1,231 @@
+use crate::snbt::MAX_DEPTH;
+use crate::writer::Writer;
+use crate::{BigEndian, Encoding, Flavor, LittleEndian, NetworkLittleEndian, Number};
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::pybacked::PyBackedBytes;
+use pyo3::types::{
+    PyBool, PyByteArray, PyBytes, PyDict, PyFloat, PyInt, PyList, PyString, PyTuple,
+};
+
+/// Serialize a Python object, inferring tag types like [`crate::loads::to_python`] produces them.
+///
+/// The root tag is written without a name if `root_name` is `None`.
+pub(crate) fn from_python(
+    obj: &Bound<'_, PyAny>,
+    root_name: Option<&str>,
+    flavor: Flavor,
+) -> PyResult<Vec<u8>> {
+    match flavor {
+        Flavor::Java => Dumper::<BigEndian>::new(obj.py())?.dump(obj, root_name),
+        Flavor::Bedrock => Dumper::<LittleEndian>::new(obj.py())?.dump(obj, root_name),
+        Flavor::BedrockNetwork => {
+            Dumper::<NetworkLittleEndian>::new(obj.py())?.dump(obj, root_name)
+        }
+    }
+}
+
+struct Dumper<'py, E> {
+    /// Wrapper classes from `nbtcompare.tags`, indexed by tag id - 1
+    wrappers: Vec<Bound<'py, PyAny>>,
+    array_type: Bound<'py, PyAny>,
+    /// Lists and dicts that are currently being written, to detect cycles
+    containers: Vec<*mut pyo3::ffi::PyObject>,
+    writer: Writer<E>,
+}
+
+impl<'py, E: Encoding> Dumper<'py, E> {
+    fn new(py: Python<'py>) -> PyResult<Self> {
+        let tags = py.import("nbtcompare.tags")?;
+        Ok(Dumper {
+            wrappers: ["Byte", "Short", "Int", "Long", "Float", "Double"]
+                .into_iter()
+                .map(|name| tags.getattr(name))
+                .collect::<PyResult<_>>()?,
+            array_type: py.import("array")?.getattr("array")?,
+            containers: Vec::new(),
+            writer: Writer::new(),
+        })
+    }
+
+    fn dump(mut self, obj: &Bound<'py, PyAny>, root_name: Option<&str>) -> PyResult<Vec<u8>> {
+        let tag_id = self.tag_id(obj)?;
+        self.writer.tag_id(tag_id);
+        if let Some(root_name) = root_name {
+            self.writer
+                .string(root_name)
+                .map_err(PyValueError::new_err)?;
+        }
+        self.payload(obj, tag_id)?;
+        Ok(self.writer.out)
+    }
+
+    fn tag_id(&self, obj: &Bound<'py, PyAny>) -> PyResult<u8> {
+        for (tag_id, wrapper) in (1..).zip(&self.wrappers) {
+            if obj.is_instance(wrapper)? {
+                return Ok(tag_id);
+            }
+        }
+        if obj.is_instance_of::<PyBool>() {
+            Ok(1)
+        } else if obj.is_instance_of::<PyInt>() {
+            Ok(if obj.extract::<i32>().is_ok() { 3 } else { 4 })
+        } else if obj.is_instance_of::<PyFloat>() {
+            Ok(6)
+        } else if obj.is_instance_of::<PyBytes>() || obj.is_instance_of::<PyByteArray>() {
+            Ok(7)
+        } else if obj.is_instance_of::<PyString>() {
+            Ok(8)
+        } else if obj.is_instance_of::<PyList>() || obj.is_instance_of::<PyTuple>() {
+            Ok(9)
+        } else if obj.is_instance_of::<PyDict>() {
+            Ok(10)
+        } else if obj.is_instance(&self.array_type)? {
+            match obj.getattr("typecode")?.extract::<String>()?.as_str() {
+                "b" | "B" => Ok(7),
+                "i" | "I" | "l" | "L" | "q" | "Q" => {
+                    match obj.getattr("itemsize")?.extract::<usize>()? {
+                        4 => Ok(11),
+                        _ => Ok(12),
+                    }
+                }
+                other => Err(PyTypeError::new_err(format!(
+                    "Cannot convert array with type code {other:?} to NBT"
+                ))),
+            }
+        } else {
+            Err(PyTypeError::new_err(format!(
+                "Cannot convert {} to NBT",
+                obj.get_type().name()?
+            )))
+        }
+    }
+
+    fn payload(&mut self, obj: &Bound<'py, PyAny>, tag_id: u8) -> PyResult<()> {
+        match tag_id {
+            1 => self
+                .writer
+                .number(Number::Int(obj.extract::<i8>()?.into()), tag_id),
+            2 => self
+                .writer
+                .number(Number::Int(obj.extract::<i16>()?.into()), tag_id),
+            3 => self
+                .writer
+                .number(Number::Int(obj.extract::<i32>()?.into()), tag_id),
+            4 => self
+                .writer
+                .number(Number::Int(obj.extract::<i64>()?), tag_id),
+            5 | 6 => self.writer.number(Number::Float(obj.extract()?), tag_id),
+            7 => {
+                let bytes = self.array_bytes(obj)?;
+                self.writer
+                    .byte_array(&bytes)
+                    .map_err(PyValueError::new_err)?;
+            }
+            8 => self
+                .writer
+                .string(&obj.extract::<String>()?)
+                .map_err(PyValueError::new_err)?,
+            9 | 10 => {
+                self.enter(obj)?;
+                match tag_id {
+                    9 => self.list(obj)?,
+                    _ => self.compound(obj)?,
+                }
+                self.containers.pop();
+            }
+            _ => {
+                let bytes = self.array_bytes(obj)?;
+                let element_size = if tag_id == 11 { 4 } else { 8 };
+                self.writer
+                    .len(bytes.len() / element_size)
+                    .map_err(PyValueError::new_err)?;
+                for element in bytes.chunks_exact(element_size) {
+                    let int = match element_size {
+                        4 => i32::from_ne_bytes(element.try_into().unwrap()).into(),
+                        _ => i64::from_ne_bytes(element.try_into().unwrap()),
+                    };
+                    self.writer.number(Number::Int(int), tag_id - 8);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Remember a list or dict that is about to be written, as nesting it in itself would never end
+    fn enter(&mut self, obj: &Bound<'py, PyAny>) -> PyResult<()> {
+        if self.containers.contains(&obj.as_ptr()) {
+            return Err(PyValueError::new_err("Circular reference"));
+        }
+        if self.containers.len() == MAX_DEPTH {
+            return Err(PyValueError::new_err(format!(
+                "Nested deeper than {MAX_DEPTH} levels"
+            )));
+        }
+        self.containers.push(obj.as_ptr());
+        Ok(())
+    }
+
+    fn list(&mut self, obj: &Bound<'py, PyAny>) -> PyResult<()> {
+        let elements: Vec<_> = obj.try_iter()?.collect::<PyResult<_>>()?;
+        let element_ids: Vec<_> = elements
+            .iter()
+            .map(|element| self.tag_id(element))
+            .collect::<PyResult<_>>()?;
+        // plain ints are widened to TAG_Long if any element of the list needs it
+        let mut widen = element_ids.contains(&4);
+        for (element, &tag_id) in elements.iter().zip(&element_ids) {
+            widen = widen && self.fits_long(element, tag_id)?;
+        }
+        let element_id = match element_ids.first() {
+            _ if widen => 4,
+            Some(&first) => first,
+            None => 0,
+        };
+        self.writer.tag_id(element_id);
+        self.writer
+            .len(elements.len())
+            .map_err(PyValueError::new_err)?;
+        for (element, tag_id) in elements.iter().zip(element_ids) {
+            if tag_id != element_id && !widen {
+                return Err(PyTypeError::new_err(
+                    "All elements of a list must have the same tag type",
+                ));
+            }
+            self.payload(element, element_id)?;
+        }
+        Ok(())
+    }
+
+    fn compound(&mut self, obj: &Bound<'py, PyAny>) -> PyResult<()> {
+        for (key, value) in obj.cast::<PyDict>()?.iter() {
+            let key = key
+                .extract::<String>()
+                .map_err(|_| PyTypeError::new_err("Keys of a compound must be str"))?;
+            let value_id = self.tag_id(&value)?;
+            self.writer.tag_id(value_id);
+            self.writer.string(&key).map_err(PyValueError::new_err)?;
+            self.payload(&value, value_id)?;
+        }
+        self.writer.tag_id(0);
+        Ok(())
+    }
+
+    /// Whether an element inferred as `tag_id` can be written as TAG_Long, which excludes wrapped ints
+    fn fits_long(&self, element: &Bound<'py, PyAny>, tag_id: u8) -> PyResult<bool> {
+        Ok(match tag_id {
+            3 => !element.is_instance(&self.wrappers[2])?,
+            4 => true,
+            _ => false,
+        })
+    }
+
+    /// The raw content of `bytes`, `bytearray` or `array.array`, in native byte order
+    fn array_bytes(&self, obj: &Bound<'py, PyAny>) -> PyResult<PyBackedBytes> {
+        if obj.is_instance(&self.array_type)? {
+            Ok(obj.call_method0("tobytes")?.extract()?)
+        } else {
+            Ok(obj.extract()?)
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use crate::RawCompound;
+use crate::chunk::{self, Container, Decoded};
+use crate::float::FloatCompare;
+use crate::ignore::Matcher;
+use crate::path::PathSegment;
+use crate::rules::{Rule, Rules};
+use crate::unordered::{elements, pair_up};
+use std::collections::HashMap;
+
+/// Compares trees according to the float mode and the path rules of the options
+#[derive(Clone, Copy)]
+pub(crate) struct Equality<'o> {
+    pub(crate) floats: FloatCompare,
+    pub(crate) rules: &'o Rules,
+    /// The `DataVersion` of the left and right root, if known
+    pub(crate) data_versions: [Option<i32>; 2],
+}
+
+impl Equality<'_> {
+    /// Like `==`, with `matcher` tracking the path within the rules
+    pub(crate) fn trees_eq(
+        &self,
+        left: &RawCompound,
+        right: &RawCompound,
+        matcher: &Matcher,
+    ) -> bool {
+        if matcher.is_empty() && self.floats.is_bitwise() {
+            return left == right;
+        }
+        match self.rules.rule(matcher) {
+            Some(Rule::Unordered(key)) => {
+                return self.unordered_eq(left, right, key.as_deref(), matcher);
+            }
+            Some(&Rule::Paletted(container)) => {
+                if let Some(eq) = self.paletted_eq(left, right, container, matcher) {
+                    return eq;
+                }
+            }
+            None => {}
+        }
+        match (left, right) {
+            (RawCompound::Mem(l_id, l), RawCompound::Mem(r_id, r)) => {
+                l_id == r_id && self.floats.payloads_eq(*l_id, l, r)
+            }
+            (RawCompound::PackedList(l_id, l), RawCompound::PackedList(r_id, r)) => {
+                l_id == r_id && self.floats.packed_eq(*l_id, l, r)
+            }
+            (RawCompound::Map(_, l), RawCompound::Map(_, r)) => {
+                l.len() == r.len()
+                    && l.iter().all(|(key, l)| {
+                        r.get(key).is_some_and(|r| {
+                            self.trees_eq(l, r, &matcher.descend(PathSegment::Key(key)))
+                        })
+                    })
+            }
+            (RawCompound::List(_, l), RawCompound::List(_, r)) => {
+                l.len() == r.len()
+                    && l.iter().zip(r).enumerate().all(|(index, (l, r))| {
+                        self.trees_eq(l, r, &matcher.descend(PathSegment::Index(index)))
+                    })
+            }
+            _ => left == right,
+        }
+    }
+
+    /// Compare two lists as multisets
+    fn unordered_eq(
+        &self,
+        left: &RawCompound,
+        right: &RawCompound,
+        key: Option<&[u8]>,
+        matcher: &Matcher,
+    ) -> bool {
+        let (mut left_unpacked, mut right_unpacked) = (Vec::new(), Vec::new());
+        let (Some(l), Some(r)) = (
+            elements(left, &mut left_unpacked),
+            elements(right, &mut right_unpacked),
+        ) else {
+            return left == right;
+        };
+        if l.len() != r.len() || left.element_id() != right.element_id() {
+            return l.is_empty() && r.is_empty();
+        }
+        let transitive = self.floats.is_transitive();
+        let pairing = pair_up(l, r, key, transitive, |left_index, right_index| {
+            let child = matcher.descend(PathSegment::Index(left_index));
+            self.trees_eq(&l[left_index], &r[right_index], &child)
+        });
+        pairing.equal.len() == l.len()
+    }
+
+    /// Compare two containers of chunk sections entry by entry, `None` if either can not be decoded
+    pub(crate) fn paletted_eq(
+        &self,
+        left: &RawCompound,
+        right: &RawCompound,
+        container: Container,
+        matcher: &Matcher,
+    ) -> Option<bool> {
+        let (left, right) = self.decode_pair(left, right, container)?;
+        Some(
+            self.entries_eq(&left, &right, matcher)
+                && left.rest.len() == right.rest.len()
+                && left
+                    .rest
+                    .iter()
+                    .zip(&right.rest)
+                    .all(|((l_key, l), (r_key, r))| {
+                        l_key == r_key
+                            && self.trees_eq(l, r, &matcher.descend(PathSegment::Key(l_key)))
+                    }),
+        )
+    }
+
+    pub(crate) fn decode_pair<'t, 'a>(
+        &self,
+        left: &'t RawCompound<'a>,
+        right: &'t RawCompound<'a>,
+        container: Container,
+    ) -> Option<(Decoded<'t, 'a>, Decoded<'t, 'a>)> {
+        let [left_version, right_version] = self.data_versions;
+        Some((
+            chunk::decode(left, container, left_version)?,
+            chunk::decode(right, container, right_version)?,
+        ))
+    }
+
+    /// Compare the decoded entries of two containers, ignoring the rest
+    pub(crate) fn entries_eq(&self, left: &Decoded, right: &Decoded, matcher: &Matcher) -> bool {
+        let palette = matcher.descend(PathSegment::Key(left.palette_key));
+        let mut checked = HashMap::new();
+        left.indices.iter().zip(&right.indices).all(|(&l, &r)| {
+            *checked.entry((l, r)).or_insert_with(|| {
+                left.entries_eq(right, l, r, |l_entry, r_entry| {
+                    let child = palette.descend(PathSegment::Index(l.into()));
+                    self.trees_eq(l_entry, r_entry, &child)
+                })
+            })
+        })
+    }
+
+    /// Use the `DataVersion` of both roots to decode chunk sections
+    pub(crate) fn with_data_versions(self, data_versions: [Option<i32>; 2]) -> Self {
+        Equality {
+            data_versions,
+            ..self
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use crate::path::{PathSegment, format_path};
+use pyo3::create_exception;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::borrow::Cow;
+
+create_exception!(
+    nbtcompare._core,
+    NBTParseError,
+    PyValueError,
+    "Raised for malformed NBT data.\n\n\
+    `offset` is the byte offset in the (decompressed) input, \
+    `path` the path of the tag being parsed and `tag_id` its type, if known."
+);
+
+pub(crate) type ParseResult<T> = Result<T, ParseError>;
+
+#[derive(Debug)]
+enum OwnedSegment {
+    Key(Vec<u8>),
+    Index(usize),
+}
+
+#[derive(Debug)]
+pub(crate) struct ParseError {
+    message: Cow<'static, str>,
+    /// Holds the number of bytes left in the buffer until [`ParseError::at_root`] is called
+    offset: usize,
+    /// Innermost segment first until [`ParseError::at_root`] is called
+    path: Vec<OwnedSegment>,
+    tag_id: Option<u8>,
+}
+
+impl ParseError {
+    pub(crate) fn new(message: impl Into<Cow<'static, str>>, remaining: &[u8]) -> Self {
+        ParseError {
+            message: message.into(),
+            offset: remaining.len(),
+            path: Vec::new(),
+            tag_id: None,
+        }
+    }
+
+    pub(crate) fn eof(remaining: &[u8]) -> Self {
+        Self::new("Unexpected EOF", remaining)
+    }
+
+    /// Error for a string in an already parsed tree of `data`, located at `path`
+    pub(crate) fn invalid_string(string: &[u8], data: &[u8], path: &[PathSegment]) -> Self {
+        let offset = string.as_ptr().addr() - data.as_ptr().addr();
+        path.iter()
+            .rev()
+            .fold(
+                Self::new("Invalid string", &data[offset..]).with_tag(8),
+                |err, segment| err.within(*segment),
+            )
+            .at_root(data)
+    }
+
+    /// Set the tag id unless a more deeply nested tag already did
+    pub(crate) fn with_tag(mut self, tag_id: u8) -> Self {
+        self.tag_id.get_or_insert(tag_id);
+        self
+    }
+
+    pub(crate) fn within(mut self, segment: PathSegment) -> Self {
+        self.path.push(match segment {
+            PathSegment::Key(key) => OwnedSegment::Key(key.to_vec()),
+            PathSegment::Index(index) => OwnedSegment::Index(index),
+        });
+        self
+    }
+
+    /// Resolve the offset relative to the start of `data`
+    pub(crate) fn at_root(mut self, data: &[u8]) -> Self {
+        self.offset = data.len() - self.offset;
+        self.path.reverse();
+        self
+    }
+
+    pub(crate) fn into_pyerr(self, py: Python<'_>) -> PyErr {
+        let path: Vec<_> = self
+            .path
+            .iter()
+            .map(|segment| match segment {
+                OwnedSegment::Key(key) => PathSegment::Key(key),
+                OwnedSegment::Index(index) => PathSegment::Index(*index),
+            })
+            .collect();
+        let path = format_path(&path);
+        let mut message = format!("{} at offset {}", self.message, self.offset);
+        if !path.is_empty() {
+            message.push_str(&format!(" in {path}"));
+        }
+        let err = NBTParseError::new_err(message);
+        let value = err.value(py);
+        for (name, attr) in [
+            ("offset", self.offset.into_pyobject(py).unwrap().into_any()),
+            ("path", path.into_pyobject(py).unwrap().into_any()),
+            ("tag_id", self.tag_id.into_pyobject(py).unwrap()),
+        ] {
+            value.setattr(name, attr).unwrap();
+        }
+        err
+    }
+}
+
+/// Errors that can occur while the GIL is released
+pub(crate) enum Error {
+    Parse(ParseError),
+    Py(PyErr),
+}
+
+impl Error {
+    pub(crate) fn into_pyerr(self, py: Python<'_>) -> PyErr {
+        match self {
+            Error::Parse(e) => e.into_pyerr(py),
+            Error::Py(e) => e,
+        }
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
+        Error::Parse(e)
+    }
+}
+
+impl From<PyErr> for Error {
+    fn from(e: PyErr) -> Self {
+        Error::Py(e)
+    }
+}
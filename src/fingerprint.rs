@@ -0,0 +1,137 @@
+use crate::chunk::{self, Palette};
+use crate::ignore::Matcher;
+use crate::path::PathSegment;
+use crate::rules::{Rule, Rules};
+use crate::unordered::elements;
+use crate::{RawCompound, TAG_SIZE_LUT, sorted_entries};
+use blake3::Hasher;
+
+/// Hash a tree so that trees considered equal by `compare` get the same digest.
+///
+/// Compound entries are hashed in key order, packed lists like lists of separate tags.
+/// Elements of unordered lists are hashed separately and their digests sorted,
+/// containers of chunk sections like the sequence of their entries.
+pub(crate) fn fingerprint(
+    tree: &RawCompound,
+    rules: &Rules,
+    data_version: Option<i32>,
+) -> [u8; 32] {
+    let fingerprinter = Fingerprinter {
+        rules,
+        data_version,
+    };
+    fingerprinter.digest(tree, &rules.matcher())
+}
+
+struct Fingerprinter<'r> {
+    rules: &'r Rules,
+    data_version: Option<i32>,
+}
+
+impl Fingerprinter<'_> {
+    fn digest(&self, tree: &RawCompound, matcher: &Matcher) -> [u8; 32] {
+        let mut hasher = Hasher::new();
+        self.hash_tree(&mut hasher, tree, matcher);
+        hasher.finalize().into()
+    }
+
+    fn hash_tree(&self, hasher: &mut Hasher, tree: &RawCompound, matcher: &Matcher) {
+        hasher.update(&[tree.tag_id()]);
+        match self.rules.rule(matcher) {
+            Some(Rule::Unordered(_)) => {
+                let mut unpacked = Vec::new();
+                if let Some(list) = elements(tree, &mut unpacked) {
+                    let mut digests: Vec<_> = list
+                        .iter()
+                        .enumerate()
+                        .map(|(index, element)| {
+                            self.digest(element, &matcher.descend(PathSegment::Index(index)))
+                        })
+                        .collect();
+                    digests.sort_unstable();
+                    hasher.update(&[tree.element_id().unwrap_or(0)]);
+                    hash_len(hasher, digests.len());
+                    for digest in digests {
+                        hasher.update(&digest);
+                    }
+                    return;
+                }
+            }
+            Some(&Rule::Paletted(container)) => {
+                if let Some(decoded) = chunk::decode(tree, container, self.data_version) {
+                    hash_len(hasher, decoded.indices.len());
+                    match decoded.palette {
+                        Palette::Entries(entries) => {
+                            let palette = matcher.descend(PathSegment::Key(decoded.palette_key));
+                            let digests: Vec<_> = entries
+                                .iter()
+                                .enumerate()
+                                .map(|(index, entry)| {
+                                    self.digest(entry, &palette.descend(PathSegment::Index(index)))
+                                })
+                                .collect();
+                            for index in decoded.indices {
+                                hasher.update(&digests[usize::from(index)]);
+                            }
+                        }
+                        Palette::Numeric => {
+                            for index in decoded.indices {
+                                hasher.update(&index.to_le_bytes());
+                            }
+                        }
+                    }
+                    self.hash_entries(hasher, decoded.rest, matcher);
+                    return;
+                }
+            }
+            None => {}
+        }
+        match tree {
+            RawCompound::Mem(_, payload) => hash_bytes(hasher, payload),
+            RawCompound::PackedList(tag_id, list) => {
+                let tag_size = TAG_SIZE_LUT[*tag_id as usize].into();
+                hasher.update(&[*tag_id]);
+                hash_len(hasher, list.len() / tag_size);
+                for element in list.chunks_exact(tag_size) {
+                    hasher.update(&[*tag_id]);
+                    hash_bytes(hasher, element);
+                }
+            }
+            RawCompound::List(_, list) => {
+                hasher.update(&[tree.element_id().unwrap_or(0)]);
+                hash_len(hasher, list.len());
+                for (index, element) in list.iter().enumerate() {
+                    let child = matcher.descend(PathSegment::Index(index));
+                    self.hash_tree(hasher, element, &child);
+                }
+            }
+            RawCompound::Map(_, map) => {
+                self.hash_entries(hasher, sorted_entries(map, &[]), matcher);
+            }
+        }
+    }
+
+    /// Hash compound entries that are sorted by key
+    fn hash_entries(
+        &self,
+        hasher: &mut Hasher,
+        entries: Vec<(&[u8], &RawCompound)>,
+        matcher: &Matcher,
+    ) {
+        hash_len(hasher, entries.len());
+        for (key, value) in entries {
+            hash_bytes(hasher, key);
+            self.hash_tree(hasher, value, &matcher.descend(PathSegment::Key(key)));
+        }
+    }
+}
+
+/// Lengths are hashed as fixed size integers so the input stays unambiguous
+fn hash_len(hasher: &mut Hasher, len: usize) {
+    hasher.update(&(len as u64).to_le_bytes());
+}
+
+fn hash_bytes(hasher: &mut Hasher, bytes: &[u8]) {
+    hash_len(hasher, bytes.len());
+    hasher.update(bytes);
+}
@@ -0,0 +1,124 @@
+use crate::{BigEndian, Encoding, Flavor, LittleEndian, NetworkLittleEndian, Number, TAG_SIZE_LUT};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::pybacked::PyBackedStr;
+
+/// How TAG_Float and TAG_Double values are compared
+#[derive(Clone, Copy)]
+pub(crate) enum FloatMode {
+    /// Compare the encoded bytes, so `-0.0 != 0.0` and NaN payloads matter
+    Bitwise,
+    /// Compare the values, so `-0.0 == 0.0` and `NaN != NaN`
+    Ieee,
+    /// Like `math.isclose`
+    Tolerance { abs: f64, rel: f64 },
+}
+
+/// The `float_mode` argument, which still lacks the tolerances
+pub(crate) enum FloatModeName {
+    Bitwise,
+    Ieee,
+    Tolerance,
+}
+
+impl FromPyObject<'_, '_> for FloatModeName {
+    type Error = PyErr;
+
+    fn extract(obj: Borrowed<'_, '_, PyAny>) -> PyResult<Self> {
+        match &*obj.extract::<PyBackedStr>()? {
+            "bitwise" => Ok(FloatModeName::Bitwise),
+            "ieee" => Ok(FloatModeName::Ieee),
+            "tolerance" => Ok(FloatModeName::Tolerance),
+            other => Err(PyValueError::new_err(format!(
+                "Unknown float mode: {other:?}"
+            ))),
+        }
+    }
+}
+
+impl FloatMode {
+    pub(crate) fn new(name: FloatModeName, abs_tol: f64, rel_tol: f64) -> PyResult<Self> {
+        match name {
+            FloatModeName::Tolerance if abs_tol >= 0.0 && rel_tol >= 0.0 => {
+                Ok(FloatMode::Tolerance {
+                    abs: abs_tol,
+                    rel: rel_tol,
+                })
+            }
+            FloatModeName::Tolerance => {
+                Err(PyValueError::new_err("Tolerances must not be negative"))
+            }
+            _ if abs_tol != 0.0 || rel_tol != 0.0 => Err(PyValueError::new_err(
+                "abs_tol and rel_tol require float_mode=\"tolerance\"",
+            )),
+            FloatModeName::Bitwise => Ok(FloatMode::Bitwise),
+            FloatModeName::Ieee => Ok(FloatMode::Ieee),
+        }
+    }
+}
+
+/// Compares trees according to a [`FloatMode`]
+#[derive(Clone, Copy)]
+pub(crate) struct FloatCompare {
+    mode: FloatMode,
+    decode: fn(&[u8], u8) -> Number,
+}
+
+impl FloatCompare {
+    pub(crate) fn new(mode: FloatMode, flavor: Flavor) -> Self {
+        FloatCompare {
+            mode,
+            decode: match flavor {
+                Flavor::Java => BigEndian::decode_number,
+                Flavor::Bedrock => LittleEndian::decode_number,
+                Flavor::BedrockNetwork => NetworkLittleEndian::decode_number,
+            },
+        }
+    }
+
+    /// Whether tags of any type can be compared bytewise
+    pub(crate) fn is_bitwise(&self) -> bool {
+        matches!(self.mode, FloatMode::Bitwise)
+    }
+
+    /// Whether equality is transitive, which does not hold for tolerances
+    pub(crate) fn is_transitive(&self) -> bool {
+        !matches!(self.mode, FloatMode::Tolerance { .. })
+    }
+
+    /// Whether tags of this type can be compared bytewise
+    pub(crate) fn is_bytewise(&self, tag_id: u8) -> bool {
+        self.is_bitwise() || !matches!(tag_id, 5 | 6)
+    }
+
+    /// Compare the payloads of two tags of type `tag_id`
+    pub(crate) fn payloads_eq(&self, tag_id: u8, left: &[u8], right: &[u8]) -> bool {
+        if self.is_bytewise(tag_id) {
+            return left == right;
+        }
+        let (Number::Float(left), Number::Float(right)) =
+            ((self.decode)(left, tag_id), (self.decode)(right, tag_id))
+        else {
+            unreachable!("tag {tag_id} is not a float")
+        };
+        match self.mode {
+            FloatMode::Bitwise | FloatMode::Ieee => left == right,
+            FloatMode::Tolerance { abs, rel } => {
+                left == right || (left - right).abs() <= abs.max(rel * left.abs().max(right.abs()))
+            }
+        }
+    }
+
+    /// Compare the payloads of two packed lists of type `tag_id`
+    pub(crate) fn packed_eq(&self, tag_id: u8, left: &[u8], right: &[u8]) -> bool {
+        if self.is_bytewise(tag_id) {
+            return left == right;
+        }
+        let tag_size = TAG_SIZE_LUT[tag_id as usize].into();
+        left.len() == right.len()
+            && left
+                .chunks_exact(tag_size)
+                .zip(right.chunks_exact(tag_size))
+                .all(|(l, r)| self.payloads_eq(tag_id, l, r))
+    }
+}
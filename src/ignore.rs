@@ -0,0 +1,233 @@
+use crate::chunk;
+use crate::path::PathSegment;
+use crate::{RawCompound, TAG_SIZE_LUT};
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyString;
+
+enum PatternSegment {
+    Key(Vec<u8>),
+    AnyKey,
+    Index(usize),
+    AnyIndex,
+    AnyDepth,
+}
+
+impl PatternSegment {
+    fn matches(&self, segment: PathSegment) -> bool {
+        match (self, segment) {
+            (PatternSegment::Key(key), PathSegment::Key(other)) => key == other,
+            (PatternSegment::Index(index), PathSegment::Index(other)) => *index == other,
+            (PatternSegment::AnyKey, PathSegment::Key(_))
+            | (PatternSegment::AnyIndex, PathSegment::Index(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+/// A path pattern like `sections[*].SkyLight` or `**.UUID`.
+///
+/// `*` matches any key, `[*]` any list index and `**` any number of segments.
+/// Keys may be double-quoted the same way as paths reported by `diff`.
+pub(crate) struct PathPattern(Vec<PatternSegment>);
+
+impl PathPattern {
+    pub(crate) fn parse(pattern: &str) -> Result<Self, String> {
+        let mut segments = Vec::new();
+        let mut rest = pattern;
+        let mut expect_key = !rest.starts_with('[');
+        loop {
+            if let Some(index) = rest.strip_prefix('[') {
+                let (index, tail) = index.split_once(']').ok_or("unclosed '['")?;
+                segments.push(match index {
+                    "*" => PatternSegment::AnyIndex,
+                    _ => PatternSegment::Index(
+                        index
+                            .parse()
+                            .map_err(|_| format!("invalid list index {index:?}"))?,
+                    ),
+                });
+                rest = tail;
+            } else if expect_key {
+                let (key, quoted);
+                (key, quoted, rest) = split_key(rest)?;
+                segments.push(match (key.as_str(), quoted) {
+                    ("*", false) => PatternSegment::AnyKey,
+                    ("**", false) => PatternSegment::AnyDepth,
+                    ("", false) => return Err("empty key".to_string()),
+                    _ => PatternSegment::Key(key.into_bytes()),
+                });
+            } else if rest.is_empty() {
+                return Ok(PathPattern(segments));
+            } else if let Some(tail) = rest.strip_prefix('.') {
+                rest = tail;
+                expect_key = true;
+                continue;
+            } else {
+                return Err(format!("unexpected {:?}", rest.chars().next().unwrap()));
+            }
+            expect_key = false;
+        }
+    }
+}
+
+/// Split off a plain or quoted key, returning whether it was quoted
+fn split_key(data: &str) -> Result<(String, bool, &str), String> {
+    let Some(quoted) = data.strip_prefix('"') else {
+        let end = data.find(['.', '[']).unwrap_or(data.len());
+        return Ok((data[..end].to_string(), false, &data[end..]));
+    };
+    let mut key = String::new();
+    let mut chars = quoted.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((key, true, &quoted[i + 1..])),
+            '\\' => key.push(chars.next().ok_or("unterminated escape")?.1),
+            c => key.push(c),
+        }
+    }
+    Err("unclosed '\"'".to_string())
+}
+
+/// Collect the `ignore` argument of the python API into patterns
+pub(crate) fn extract_patterns(
+    ignore: Option<&Bound<'_, PyAny>>,
+    exclude_last_update: bool,
+    ignore_data_version: bool,
+) -> PyResult<Vec<PathPattern>> {
+    let mut patterns = Vec::new();
+    if exclude_last_update {
+        patterns.push(PathPattern(vec![PatternSegment::Key(
+            b"LastUpdate".to_vec(),
+        )]));
+    }
+    if ignore_data_version {
+        patterns.push(PathPattern(vec![PatternSegment::Key(
+            chunk::DATA_VERSION.to_vec(),
+        )]));
+    }
+    let Some(ignore) = ignore else {
+        return Ok(patterns);
+    };
+    if ignore.is_instance_of::<PyString>() {
+        return Err(PyTypeError::new_err(
+            "ignore must be a collection of patterns, not a single str",
+        ));
+    }
+    for pattern in ignore.try_iter()? {
+        let pattern: String = pattern?.extract()?;
+        patterns.push(PathPattern::parse(&pattern).map_err(|e| {
+            PyValueError::new_err(format!("Invalid ignore pattern {pattern:?}: {e}"))
+        })?);
+    }
+    Ok(patterns)
+}
+
+/// Positions within the patterns that the current path is matched up to
+#[derive(Clone)]
+pub(crate) struct Matcher<'p> {
+    patterns: &'p [PathPattern],
+    states: Vec<(usize, usize)>,
+}
+
+impl<'p> Matcher<'p> {
+    pub(crate) fn new(patterns: &'p [PathPattern]) -> Self {
+        let mut matcher = Matcher {
+            patterns,
+            states: Vec::new(),
+        };
+        for pattern in 0..patterns.len() {
+            matcher.add_state(pattern, 0);
+        }
+        matcher
+    }
+
+    /// Whether nothing below the current path can be ignored
+    pub(crate) fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    /// The matcher for a child of the current path, or `None` if the child is ignored
+    pub(crate) fn child(&self, segment: PathSegment) -> Option<Self> {
+        let child = self.descend(segment);
+        child.matched().is_none().then_some(child)
+    }
+
+    /// The matcher for a child of the current path, even if a pattern matches it
+    pub(crate) fn descend(&self, segment: PathSegment) -> Self {
+        let mut child = Matcher {
+            patterns: self.patterns,
+            states: Vec::new(),
+        };
+        for &(pattern, position) in &self.states {
+            match self.patterns[pattern].0.get(position) {
+                Some(PatternSegment::AnyDepth) => child.add_state(pattern, position),
+                Some(seg) if seg.matches(segment) => child.add_state(pattern, position + 1),
+                _ => {}
+            }
+        }
+        child
+    }
+
+    /// The index of the first pattern that matches the current path
+    pub(crate) fn matched(&self) -> Option<usize> {
+        self.states
+            .iter()
+            .filter(|&&(pattern, position)| position == self.patterns[pattern].0.len())
+            .map(|&(pattern, _)| pattern)
+            .min()
+    }
+
+    fn add_state(&mut self, pattern: usize, position: usize) {
+        if self.states.contains(&(pattern, position)) {
+            return;
+        }
+        self.states.push((pattern, position));
+        // "**" may also match zero segments
+        if let Some(PatternSegment::AnyDepth) = self.patterns[pattern].0.get(position) {
+            self.add_state(pattern, position + 1);
+        }
+    }
+}
+
+/// Remove everything matched by `matcher` from the tree.
+/// Numeric lists are unpacked, even if none of their elements are ignored,
+/// so they have the same representation as on the other side, where elements might have been ignored.
+pub(crate) fn prune(tree: &mut RawCompound, matcher: &Matcher) {
+    if matcher.is_empty() {
+        return;
+    }
+    match tree {
+        RawCompound::Map(_, map) => {
+            map.retain(|key, value| match matcher.child(PathSegment::Key(key)) {
+                Some(child) => {
+                    prune(value, &child);
+                    true
+                }
+                None => false,
+            })
+        }
+        RawCompound::List(_, list) => {
+            let mut index = 0;
+            list.retain_mut(|value| {
+                let child = matcher.child(PathSegment::Index(index));
+                index += 1;
+                child.map(|child| prune(value, &child)).is_some()
+            })
+        }
+        RawCompound::PackedList(tag_id, payload) => {
+            let tag_size = TAG_SIZE_LUT[*tag_id as usize] as usize;
+            if tag_size == 0 {
+                return;
+            }
+            let kept = payload
+                .chunks_exact(tag_size)
+                .enumerate()
+                .filter(|(index, _)| matcher.child(PathSegment::Index(*index)).is_some())
+                .map(|(_, element)| RawCompound::Mem(*tag_id, element))
+                .collect();
+            *tree = RawCompound::List(payload, kept);
+        }
+        RawCompound::Mem(..) => {}
+    }
+}
@@ -1,168 +1,1333 @@
-use pyo3::exceptions::{PyOverflowError, PyValueError};
+use compression::{Compression, decompress};
+use equality::Equality;
+use error::{Error, ParseError, ParseResult};
+use float::{FloatCompare, FloatMode};
+use ignore::{Matcher, PathPattern};
+use path::PathSegment;
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
+use pyo3::pybacked::{PyBackedBytes, PyBackedStr};
+use rules::Rules;
+use std::borrow::Cow;
 use std::collections::HashMap;
-use std::io;
-use std::io::{Error, ErrorKind};
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::{panic, thread};
 
-#[derive(PartialEq)]
+mod chunk;
+mod compression;
+mod diff;
+mod dumps;
+mod equality;
+mod error;
+mod fingerprint;
+mod float;
+mod ignore;
+mod loads;
+mod path;
+mod region;
+mod rules;
+mod snbt;
+mod stats;
+mod stream;
+mod unordered;
+mod writer;
+
+/// Borrowed NBT tree. `Mem` and `PackedList` carry the tag id (element tag id for `PackedList`),
+/// `Map` and `List` carry the encoded payload they were parsed from.
 enum RawCompound<'a> {
-    Mem(&'a [u8]),
-    Map(HashMap<&'a [u8], RawCompound<'a>>),
-    List(Vec<RawCompound<'a>>),
-}
-type ParseFuncType = for<'a> fn(&mut &'a [u8]) -> PyResult<RawCompound<'a>>;
-
-const TAG_LUT: [Option<ParseFuncType>; 13] = [
-    None,                       //  TAG_End
-    Some(get_raw_numeric::<1>), //  TAG_Byte
-    Some(get_raw_numeric::<2>), //  TAG_Short
-    Some(get_raw_numeric::<4>), //  TAG_Int
-    Some(get_raw_numeric::<8>), //  TAG_Long
-    Some(get_raw_numeric::<4>), //  TAG_Float
-    Some(get_raw_numeric::<8>), //  TAG_Double
-    Some(get_raw_array::<1>),   //  TAG_Byte_Array
-    Some(get_raw_string),       //  TAG_String
-    Some(get_raw_list),         //  TAG_List
-    Some(get_raw_compound),     //  TAG_Compound
-    Some(get_raw_array::<4>),   //  TAG_Int_Array
-    Some(get_raw_array::<8>),   //  TAG_Long_Array
-];
+    Mem(u8, &'a [u8]),
+    PackedList(u8, &'a [u8]),
+    Map(&'a [u8], HashMap<&'a [u8], RawCompound<'a>>),
+    List(&'a [u8], Vec<RawCompound<'a>>),
+}
+
+impl<'a> RawCompound<'a> {
+    fn tag_id(&self) -> u8 {
+        match self {
+            RawCompound::Mem(tag_id, _) => *tag_id,
+            RawCompound::PackedList(..) | RawCompound::List(..) => 9,
+            RawCompound::Map(..) => 10,
+        }
+    }
+
+    fn element_id(&self) -> Option<u8> {
+        match self {
+            RawCompound::PackedList(tag_id, _) => Some(*tag_id),
+            RawCompound::List(_, list) => list.first().map(RawCompound::tag_id),
+            _ => None,
+        }
+    }
+
+    fn payload(&self) -> &'a [u8] {
+        match self {
+            RawCompound::Mem(_, payload)
+            | RawCompound::PackedList(_, payload)
+            | RawCompound::Map(payload, _)
+            | RawCompound::List(payload, _) => payload,
+        }
+    }
+}
+
+// the encoded payload of containers is ignored, so key order does not matter
+impl PartialEq for RawCompound<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (RawCompound::Mem(l_id, l), RawCompound::Mem(r_id, r))
+            | (RawCompound::PackedList(l_id, l), RawCompound::PackedList(r_id, r)) => {
+                l_id == r_id && l == r
+            }
+            (RawCompound::Map(_, l), RawCompound::Map(_, r)) => l == r,
+            (RawCompound::List(_, l), RawCompound::List(_, r)) => l == r,
+            _ => false,
+        }
+    }
+}
+
+/// The entries of a compound sorted by key, leaving out the `except` keys
+fn sorted_entries<'t, 'a>(
+    map: &'t HashMap<&'a [u8], RawCompound<'a>>,
+    except: &[&[u8]],
+) -> Vec<(&'a [u8], &'t RawCompound<'a>)> {
+    let mut entries: Vec<_> = map
+        .iter()
+        .filter(|(key, _)| !except.contains(key))
+        .map(|(key, value)| (*key, value))
+        .collect();
+    entries.sort_unstable_by_key(|(key, _)| *key);
+    entries
+}
+
+type ParseFuncType = for<'a> fn(&mut &'a [u8]) -> ParseResult<RawCompound<'a>>;
+
+/// The NBT flavors understood by the parser
+#[derive(Clone, Copy)]
+enum Flavor {
+    Java,
+    Bedrock,
+    BedrockNetwork,
+}
+
+impl FromPyObject<'_, '_> for Flavor {
+    type Error = PyErr;
+
+    fn extract(obj: Borrowed<'_, '_, PyAny>) -> PyResult<Self> {
+        match &*obj.extract::<PyBackedStr>()? {
+            "java" => Ok(Flavor::Java),
+            "bedrock" => Ok(Flavor::Bedrock),
+            "bedrock_network" => Ok(Flavor::BedrockNetwork),
+            other => Err(PyValueError::new_err(format!("Unknown flavor: {other:?}"))),
+        }
+    }
+}
+
+/// How lengths and numbers are encoded on the wire.
+/// Numbers are compared bytewise, so their byte order does not matter while parsing.
+trait Encoding: Sized {
+    const TAG_LUT: [Option<ParseFuncType>; 13] = tag_lut::<Self>();
+
+    fn get_str_len(data: &mut &[u8]) -> ParseResult<usize>;
+    fn get_len(data: &mut &[u8]) -> ParseResult<u32>;
+
+    /// Whether all values of a numeric tag have the same size
+    fn is_fixed_width(_tag_id: u8) -> bool {
+        true
+    }
+
+    fn split_off_number<'a>(data: &mut &'a [u8], tag_id: u8) -> ParseResult<&'a [u8]> {
+        split_off(data, TAG_SIZE_LUT[tag_id as usize].into())
+    }
+
+    /// Decode a number split off by [`Encoding::split_off_number`]
+    fn decode_number(num: &[u8], tag_id: u8) -> Number;
+
+    /// Decode the payload of a TAG_String, returning `None` if it is malformed
+    fn decode_string(string: &[u8]) -> Option<Cow<'_, str>> {
+        std::str::from_utf8(string).ok().map(Cow::Borrowed)
+    }
+
+    /// Longest encoded string the length prefix can describe
+    const MAX_STR_LEN: usize = u16::MAX as usize;
+
+    fn put_str_len(out: &mut Vec<u8>, len: usize);
+    fn put_len(out: &mut Vec<u8>, len: u32);
+
+    /// Inverse of [`Encoding::decode_number`]. `num` has to be in range for `tag_id`
+    fn put_number(out: &mut Vec<u8>, num: Number, tag_id: u8);
+
+    fn encode_string(string: &str) -> Cow<'_, [u8]> {
+        Cow::Borrowed(string.as_bytes())
+    }
+}
+
+struct BigEndian;
+struct LittleEndian;
+/// Bedrock's network format, which uses VarInts for lengths, ints and longs
+struct NetworkLittleEndian;
+
+impl Encoding for BigEndian {
+    fn get_str_len(data: &mut &[u8]) -> ParseResult<usize> {
+        Ok(u16::from_be_bytes(split_off_chunk(data)?).into())
+    }
+
+    fn get_len(data: &mut &[u8]) -> ParseResult<u32> {
+        Ok(u32::from_be_bytes(split_off_chunk(data)?))
+    }
+
+    fn decode_number(num: &[u8], tag_id: u8) -> Number {
+        decode_be_number(num, tag_id)
+    }
+
+    fn decode_string(string: &[u8]) -> Option<Cow<'_, str>> {
+        decode_mutf8(string)
+    }
+
+    fn put_str_len(out: &mut Vec<u8>, len: usize) {
+        out.extend_from_slice(&(len as u16).to_be_bytes());
+    }
+
+    fn put_len(out: &mut Vec<u8>, len: u32) {
+        out.extend_from_slice(&len.to_be_bytes());
+    }
+
+    fn put_number(out: &mut Vec<u8>, num: Number, tag_id: u8) {
+        put_be_number(out, num, tag_id);
+    }
+
+    fn encode_string(string: &str) -> Cow<'_, [u8]> {
+        encode_mutf8(string)
+    }
+}
+
+impl Encoding for LittleEndian {
+    fn get_str_len(data: &mut &[u8]) -> ParseResult<usize> {
+        Ok(u16::from_le_bytes(split_off_chunk(data)?).into())
+    }
+
+    fn get_len(data: &mut &[u8]) -> ParseResult<u32> {
+        Ok(u32::from_le_bytes(split_off_chunk(data)?))
+    }
+
+    fn decode_number(num: &[u8], tag_id: u8) -> Number {
+        let mut buf = [0; 8];
+        let buf = &mut buf[..num.len()];
+        buf.copy_from_slice(num);
+        buf.reverse();
+        decode_be_number(buf, tag_id)
+    }
+
+    fn put_str_len(out: &mut Vec<u8>, len: usize) {
+        out.extend_from_slice(&(len as u16).to_le_bytes());
+    }
+
+    fn put_len(out: &mut Vec<u8>, len: u32) {
+        out.extend_from_slice(&len.to_le_bytes());
+    }
+
+    fn put_number(out: &mut Vec<u8>, num: Number, tag_id: u8) {
+        let start = out.len();
+        put_be_number(out, num, tag_id);
+        out[start..].reverse();
+    }
+}
+
+impl Encoding for NetworkLittleEndian {
+    fn get_str_len(data: &mut &[u8]) -> ParseResult<usize> {
+        Ok(get_varint(data, 5)? as usize)
+    }
+
+    fn get_len(data: &mut &[u8]) -> ParseResult<u32> {
+        let len = zigzag_decode(get_varint(data, 5)?);
+        u32::try_from(len).map_err(|_| ParseError::new("Negative length", data))
+    }
+
+    fn is_fixed_width(tag_id: u8) -> bool {
+        !matches!(tag_id, 3 | 4)
+    }
+
+    fn split_off_number<'a>(data: &mut &'a [u8], tag_id: u8) -> ParseResult<&'a [u8]> {
+        match tag_id {
+            3 => split_off_varint(data, 5),
+            4 => split_off_varint(data, 10),
+            _ => split_off(data, TAG_SIZE_LUT[tag_id as usize].into()),
+        }
+    }
+
+    fn decode_number(mut num: &[u8], tag_id: u8) -> Number {
+        match tag_id {
+            3 | 4 => Number::Int(zigzag_decode(get_varint(&mut num, 10).unwrap())),
+            _ => LittleEndian::decode_number(num, tag_id),
+        }
+    }
+
+    const MAX_STR_LEN: usize = u32::MAX as usize;
+
+    fn put_str_len(out: &mut Vec<u8>, len: usize) {
+        put_varint(out, len as u64);
+    }
+
+    fn put_len(out: &mut Vec<u8>, len: u32) {
+        put_varint(out, zigzag_encode(len.into()));
+    }
+
+    fn put_number(out: &mut Vec<u8>, num: Number, tag_id: u8) {
+        match (num, tag_id) {
+            (Number::Int(int), 3 | 4) => put_varint(out, zigzag_encode(int)),
+            _ => LittleEndian::put_number(out, num, tag_id),
+        }
+    }
+}
+
+/// A decoded numeric tag
+#[derive(Clone, Copy)]
+enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+fn decode_be_number(num: &[u8], tag_id: u8) -> Number {
+    match tag_id {
+        1 => Number::Int(i8::from_be_bytes(num.try_into().unwrap()).into()),
+        2 => Number::Int(i16::from_be_bytes(num.try_into().unwrap()).into()),
+        3 => Number::Int(i32::from_be_bytes(num.try_into().unwrap()).into()),
+        4 => Number::Int(i64::from_be_bytes(num.try_into().unwrap())),
+        5 => Number::Float(f32::from_be_bytes(num.try_into().unwrap()).into()),
+        6 => Number::Float(f64::from_be_bytes(num.try_into().unwrap())),
+        _ => unreachable!("tag {tag_id} is not numeric"),
+    }
+}
+
+fn put_be_number(out: &mut Vec<u8>, num: Number, tag_id: u8) {
+    match (num, tag_id) {
+        (Number::Int(int), 1) => out.push(int as u8),
+        (Number::Int(int), 2) => out.extend_from_slice(&(int as i16).to_be_bytes()),
+        (Number::Int(int), 3) => out.extend_from_slice(&(int as i32).to_be_bytes()),
+        (Number::Int(int), 4) => out.extend_from_slice(&int.to_be_bytes()),
+        (Number::Float(float), 5) => out.extend_from_slice(&(float as f32).to_be_bytes()),
+        (Number::Float(float), 6) => out.extend_from_slice(&float.to_be_bytes()),
+        _ => unreachable!("number does not match tag {tag_id}"),
+    }
+}
+
+/// Iterate over the numbers of an array or packed list payload
+fn numbers<E: Encoding>(mut payload: &[u8], tag_id: u8) -> impl Iterator<Item = Number> {
+    std::iter::from_fn(move || {
+        (!payload.is_empty())
+            .then(|| E::decode_number(E::split_off_number(&mut payload, tag_id).unwrap(), tag_id))
+    })
+}
+
+/// Java edition encodes NUL as two bytes and supplementary characters as surrogate pairs
+fn decode_mutf8(string: &[u8]) -> Option<Cow<'_, str>> {
+    if let Ok(string) = std::str::from_utf8(string) {
+        return Some(Cow::Borrowed(string));
+    }
+    let continuation = |byte: Option<&u8>| {
+        byte.filter(|byte| *byte & 0xc0 == 0x80)
+            .map(|byte| u16::from(byte & 0x3f))
+    };
+    let mut units = Vec::with_capacity(string.len());
+    let mut bytes = string.iter();
+    while let Some(&byte) = bytes.next() {
+        units.push(match byte {
+            0x00..=0x7f => byte.into(),
+            0xc0..=0xdf => (u16::from(byte & 0x1f) << 6) | continuation(bytes.next())?,
+            0xe0..=0xef => {
+                (u16::from(byte & 0x0f) << 12)
+                    | (continuation(bytes.next())? << 6)
+                    | continuation(bytes.next())?
+            }
+            _ => return None,
+        });
+    }
+    String::from_utf16(&units).ok().map(Cow::Owned)
+}
+
+fn encode_mutf8(string: &str) -> Cow<'_, [u8]> {
+    // NUL and 4 byte sequences are the only differences to UTF-8
+    if !string.bytes().any(|byte| byte == 0 || byte >= 0xf0) {
+        return Cow::Borrowed(string.as_bytes());
+    }
+    let mut out = Vec::with_capacity(string.len() + 2);
+    for unit in string.encode_utf16() {
+        match unit {
+            0x01..=0x7f => out.push(unit as u8),
+            0x00 | 0x80..=0x7ff => {
+                out.extend_from_slice(&[0xc0 | (unit >> 6) as u8, 0x80 | (unit & 0x3f) as u8])
+            }
+            _ => out.extend_from_slice(&[
+                0xe0 | (unit >> 12) as u8,
+                0x80 | ((unit >> 6) & 0x3f) as u8,
+                0x80 | (unit & 0x3f) as u8,
+            ]),
+        }
+    }
+    Cow::Owned(out)
+}
+
+const fn tag_lut<E: Encoding>() -> [Option<ParseFuncType>; 13] {
+    [
+        None,                            //  TAG_End
+        Some(get_raw_numeric::<E, 1>),   //  TAG_Byte
+        Some(get_raw_numeric::<E, 2>),   //  TAG_Short
+        Some(get_raw_numeric::<E, 3>),   //  TAG_Int
+        Some(get_raw_numeric::<E, 4>),   //  TAG_Long
+        Some(get_raw_numeric::<E, 5>),   //  TAG_Float
+        Some(get_raw_numeric::<E, 6>),   //  TAG_Double
+        Some(get_raw_array::<E, 7, 1>),  //  TAG_Byte_Array
+        Some(get_raw_string::<E>),       //  TAG_String
+        Some(get_raw_list::<E>),         //  TAG_List
+        Some(get_raw_compound::<E>),     //  TAG_Compound
+        Some(get_raw_array::<E, 11, 3>), //  TAG_Int_Array
+        Some(get_raw_array::<E, 12, 4>), //  TAG_Long_Array
+    ]
+}
 const TAG_SIZE_LUT: [u8; 7] = [0, 1, 2, 4, 8, 4, 8];
 
-fn get_raw_numeric<'a, const N: usize>(data: &mut &'a [u8]) -> PyResult<RawCompound<'a>> {
-    let num = split_off(data, N)?;
-    Ok(RawCompound::Mem(num))
+fn get_raw_numeric<'a, E: Encoding, const ID: u8>(
+    data: &mut &'a [u8],
+) -> ParseResult<RawCompound<'a>> {
+    let num = E::split_off_number(data, ID)?;
+    Ok(RawCompound::Mem(ID, num))
 }
 
-fn get_raw_array<'a, const N: usize>(data: &mut &'a [u8]) -> PyResult<RawCompound<'a>> {
-    let arr_len = u32::from_be_bytes(split_off_chunk(data)?);
+fn get_raw_array<'a, E: Encoding, const ID: u8, const ELEMENT_ID: u8>(
+    data: &mut &'a [u8],
+) -> ParseResult<RawCompound<'a>> {
+    let arr_len = E::get_len(data)?;
+    if !E::is_fixed_width(ELEMENT_ID) {
+        let start = *data;
+        for _ in 0..arr_len {
+            E::split_off_number(data, ELEMENT_ID)?;
+        }
+        return Ok(RawCompound::Mem(ID, consumed(start, data)));
+    }
     let byte_len = (arr_len as usize)
-        .checked_mul(N)
-        .ok_or(PyOverflowError::new_err(
-            "Overflow when calculating array length \
-            (consider using a 64 bit version of this package)",
-        ))?;
-    Ok(RawCompound::Mem(split_off(data, byte_len)?))
+        .checked_mul(TAG_SIZE_LUT[ELEMENT_ID as usize].into())
+        .ok_or_else(|| {
+            ParseError::new(
+                "Overflow when calculating array length \
+                (consider using a 64 bit version of this package)",
+                data,
+            )
+        })?;
+    Ok(RawCompound::Mem(ID, split_off(data, byte_len)?))
 }
 
-fn get_raw_string<'a>(data: &mut &'a [u8]) -> PyResult<RawCompound<'a>> {
-    let length = get_u16(data)? as usize;
-    Ok(RawCompound::Mem(split_off(data, length)?))
+fn get_raw_string<'a, E: Encoding>(data: &mut &'a [u8]) -> ParseResult<RawCompound<'a>> {
+    let length = E::get_str_len(data)?;
+    Ok(RawCompound::Mem(8, split_off(data, length)?))
 }
 
-fn get_raw_list<'a>(data: &mut &'a [u8]) -> PyResult<RawCompound<'a>> {
+fn get_raw_list<'a, E: Encoding>(data: &mut &'a [u8]) -> ParseResult<RawCompound<'a>> {
     let tag_id = get_u8(data)?;
-    let size = u32::from_be_bytes(split_off_chunk(data)?);
-    if tag_id < 7 {
+    let size = E::get_len(data)?;
+    // empty lists are often written with TAG_End as element type, so they all compare equal
+    if size == 0 {
+        return Ok(RawCompound::List(&[], Vec::new()));
+    }
+    if tag_id == 0 {
+        return Err(ParseError::new(
+            "Missing element type of non-empty list",
+            data,
+        ));
+    }
+    if tag_id < 7 && E::is_fixed_width(tag_id) {
         let tag_size: usize = TAG_SIZE_LUT[tag_id as usize].into();
-        let arr_byte_len = tag_size
-            .checked_mul(size as usize)
-            .ok_or(PyOverflowError::new_err(
+        let arr_byte_len = tag_size.checked_mul(size as usize).ok_or_else(|| {
+            ParseError::new(
                 "Overflow when calculating list length \
-            (consider using a 64 bit version of this package)",
-            ))?;
-        return Ok(RawCompound::Mem(split_off(data, arr_byte_len)?));
+                    (consider using a 64 bit version of this package)",
+                data,
+            )
+        })?;
+        return Ok(RawCompound::PackedList(
+            tag_id,
+            split_off(data, arr_byte_len)?,
+        ));
     }
-    let parse_func = TAG_LUT
+    let parse_func = E::TAG_LUT
         .get(tag_id as usize)
-        .ok_or_else(|| PyValueError::new_err(format!("Unknown tag id: {tag_id}")))?
+        .ok_or_else(|| ParseError::new(format!("Unknown tag id: {tag_id}"), data).with_tag(tag_id))?
         .unwrap();
-    let mut res = Vec::with_capacity(size as usize);
-    for _ in 0..size {
-        res.push(parse_func(data)?)
+    let start = *data;
+    // every element takes at least one byte, so a larger size runs into the end of the data
+    let mut res = Vec::with_capacity((size as usize).min(data.len()));
+    for index in 0..size as usize {
+        res.push(
+            parse_func(data).map_err(|e| e.with_tag(tag_id).within(PathSegment::Index(index)))?,
+        )
     }
 
-    Ok(RawCompound::List(res))
+    Ok(RawCompound::List(consumed(start, data), res))
 }
 
-fn get_raw_compound<'a>(data: &mut &'a [u8]) -> PyResult<RawCompound<'a>> {
+fn get_raw_compound<'a, E: Encoding>(data: &mut &'a [u8]) -> ParseResult<RawCompound<'a>> {
+    let start = *data;
     let mut map = HashMap::new();
-    while let Some(parse_func) = TAG_LUT
-        .get(get_u8(data)? as usize)
-        .ok_or(PyValueError::new_err("Unknown tag"))?
-    {
-        let name_len = get_u16(data)?;
-        let name = split_off(data, name_len.into())?;
-        let compound = parse_func(data)?;
+    loop {
+        let tag_start = *data;
+        let tag_id = get_u8(data)?;
+        let Some(parse_func) = E::TAG_LUT.get(tag_id as usize).ok_or_else(|| {
+            ParseError::new(format!("Unknown tag id: {tag_id}"), tag_start).with_tag(tag_id)
+        })?
+        else {
+            break;
+        };
+        let name = E::get_str_len(data)
+            .and_then(|name_len| split_off(data, name_len))
+            .map_err(|e| e.with_tag(tag_id))?;
+        let compound =
+            parse_func(data).map_err(|e| e.with_tag(tag_id).within(PathSegment::Key(name)))?;
         map.insert(name, compound);
     }
-    Ok(RawCompound::Map(map))
+    Ok(RawCompound::Map(consumed(start, data), map))
 }
 
-fn load_nbt_raw(data: &'_ [u8]) -> PyResult<RawCompound<'_>> {
+fn load_nbt_raw<'a>(data: &'a [u8], options: &ParseOptions) -> ParseResult<RawCompound<'a>> {
+    match options.flavor {
+        Flavor::Java => load_root::<BigEndian>(data, options),
+        Flavor::Bedrock => load_root::<LittleEndian>(strip_bedrock_header(data), options),
+        Flavor::BedrockNetwork => load_root::<NetworkLittleEndian>(data, options),
+    }
+    .map_err(|e| e.at_root(data))
+}
+
+fn load_root<'a, E: Encoding>(
+    data: &'a [u8],
+    options: &ParseOptions,
+) -> ParseResult<RawCompound<'a>> {
     let mut data = data;
-    if get_u8(&mut data)? != 10 {
-        return Err(PyValueError::new_err("Root TAG is not compound"));
+    let (tag_id, parse_func) = read_root_header::<E>(&mut data, options)?;
+    parse_func(&mut data).map_err(|e| e.with_tag(tag_id))
+}
+
+/// Read the id and name of the root tag
+fn read_root_header<E: Encoding>(
+    data: &mut &[u8],
+    options: &ParseOptions,
+) -> ParseResult<(u8, ParseFuncType)> {
+    let root_start = *data;
+    let tag_id = get_u8(data)?;
+    if options.strict_root && tag_id != 10 {
+        return Err(ParseError::new("Root TAG is not compound", root_start).with_tag(tag_id));
+    }
+    let parse_func = E::TAG_LUT
+        .get(tag_id as usize)
+        .copied()
+        .flatten()
+        .ok_or_else(|| {
+            ParseError::new(format!("Invalid root tag id: {tag_id}"), root_start).with_tag(tag_id)
+        })?;
+    // network NBT omits the name of the root tag
+    if !options.nameless_root {
+        let name_len = E::get_str_len(data)?;
+        split_off(data, name_len)?;
+    }
+    Ok((tag_id, parse_func))
+}
+
+/// Bedrock's level.dat starts with the storage version and the length of the remaining data
+fn strip_bedrock_header(data: &[u8]) -> &[u8] {
+    match data.split_at_checked(8) {
+        Some((header, rest))
+            if u32::from_le_bytes(header[4..].try_into().unwrap()) as usize == rest.len() =>
+        {
+            rest
+        }
+        _ => data,
     }
-    let name_len = get_u16(&mut data)?;
-    let _ = data.split_off(..name_len.into());
-    get_raw_compound(&mut data)
 }
 
 // Helper Functions
 
-fn split_off<'a>(data: &mut &'a [u8], amount: usize) -> io::Result<&'a [u8]> {
-    let name = data
-        .split_off(..amount)
-        .ok_or(Error::new(ErrorKind::UnexpectedEof, "Unexpected EOF"))?;
-    Ok(name)
+fn split_off<'a>(data: &mut &'a [u8], amount: usize) -> ParseResult<&'a [u8]> {
+    let remaining = *data;
+    data.split_off(..amount)
+        .ok_or_else(|| ParseError::eof(remaining))
 }
 
-fn get_u16(data: &mut &[u8]) -> io::Result<u16> {
-    Ok(u16::from_be_bytes(split_off_chunk(data)?))
+fn consumed<'a>(start: &'a [u8], rest: &[u8]) -> &'a [u8] {
+    &start[..start.len() - rest.len()]
 }
 
-fn get_u8(data: &mut &[u8]) -> io::Result<u8> {
-    Ok(*data
-        .split_off_first()
-        .ok_or(Error::new(ErrorKind::UnexpectedEof, "Unexpected EOF"))?)
+/// Read an unsigned LEB128 VarInt of at most `max_len` bytes
+fn get_varint(data: &mut &[u8], max_len: usize) -> ParseResult<u64> {
+    Ok(split_off_varint(data, max_len)?
+        .iter()
+        .rev()
+        .fold(0, |acc, byte| (acc << 7) | u64::from(byte & 0x7f)))
 }
 
-fn split_off_chunk<const N: usize>(data: &mut &[u8]) -> io::Result<[u8; N]> {
+fn split_off_varint<'a>(data: &mut &'a [u8], max_len: usize) -> ParseResult<&'a [u8]> {
+    let len = data
+        .iter()
+        .take(max_len)
+        .position(|byte| byte & 0x80 == 0)
+        .ok_or_else(|| match data.len() < max_len {
+            true => ParseError::eof(data),
+            false => ParseError::new("VarInt is too long", data),
+        })?;
+    split_off(data, len + 1)
+}
+
+fn zigzag_decode(value: u64) -> i64 {
+    (value >> 1) as i64 ^ -((value & 1) as i64)
+}
+
+fn zigzag_encode(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn put_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn get_u8(data: &mut &[u8]) -> ParseResult<u8> {
+    let remaining = *data;
+    data.split_off_first()
+        .copied()
+        .ok_or_else(|| ParseError::eof(remaining))
+}
+
+fn split_off_chunk<const N: usize>(data: &mut &[u8]) -> ParseResult<[u8; N]> {
     let res: &[u8; N];
     (res, *data) = data
         .split_first_chunk()
-        .ok_or(Error::new(ErrorKind::UnexpectedEof, "Unexpected EOF"))?;
+        .ok_or_else(|| ParseError::eof(data))?;
     Ok(*res)
 }
 
 #[pymodule]
 mod _core {
-    use super::do_compare;
+    use super::chunk::{self, block_sections, diff_sections, sections_to_python};
+    use super::compression::{Compression, decompress};
+    use super::dumps::from_python;
+    use super::error::Error;
+    use super::float::{FloatMode, FloatModeName};
+    use super::ignore::extract_patterns;
+    use super::loads::to_python;
+    use super::region::{Region, compare_regions};
+    use super::rules::extract_rules;
+    use super::snbt;
+    use super::stats;
+    use super::stream;
+    use super::{
+        Flavor, Options, ParseOptions, SideResult, both, do_compare, do_compare_many, do_diff,
+        do_fingerprint, load_nbt_raw, thread_count,
+    };
+    use pyo3::IntoPyObjectExt;
     use pyo3::prelude::*;
+    use pyo3::pybacked::PyBackedBytes;
+    use pyo3::types::{IntoPyDict, PyBytes, PyDict};
+    use std::borrow::Cow;
+
+    #[pymodule_export]
+    use super::diff::Difference;
+    #[pymodule_export]
+    use super::error::NBTParseError;
+    #[pymodule_export]
+    use super::region::RegionComparison;
+
+    #[pyfunction]
+    #[pyo3(signature = (
+        left,
+        right,
+        exclude_last_update = false,
+        *,
+        ignore = None,
+        ignore_data_version = false,
+        report_data_versions = false,
+        unordered = None,
+        chunk_aware = false,
+        compression = Compression::Auto,
+        flavor = Flavor::Java,
+        nameless_root = false,
+        strict_root = false,
+        float_mode = FloatModeName::Bitwise,
+        abs_tol = 0.0,
+        rel_tol = 0.0,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn compare<'py>(
+        py: Python<'py>,
+        left: &[u8],
+        right: &[u8],
+        exclude_last_update: bool,
+        ignore: Option<&Bound<'_, PyAny>>,
+        ignore_data_version: bool,
+        report_data_versions: bool,
+        unordered: Option<&Bound<'_, PyAny>>,
+        chunk_aware: bool,
+        compression: Compression,
+        flavor: Flavor,
+        nameless_root: bool,
+        strict_root: bool,
+        float_mode: FloatModeName,
+        abs_tol: f64,
+        rel_tol: f64,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let float_mode = FloatMode::new(float_mode, abs_tol, rel_tol)?;
+        let parse = ParseOptions::new(compression, flavor, nameless_root, strict_root);
+        let options = compare_options(
+            ignore,
+            exclude_last_update,
+            ignore_data_version,
+            unordered,
+            chunk_aware,
+            parse,
+            float_mode,
+        )?;
+        let (equal, data_versions) = py
+            .detach(|| do_compare(left, right, &options, report_data_versions))
+            .map_err(|err| add_side_note(py, err))?;
+        with_data_versions(py, equal, data_versions, report_data_versions)
+    }
+
+    #[pyfunction]
+    #[pyo3(signature = (
+        pairs,
+        exclude_last_update = false,
+        *,
+        ignore = None,
+        ignore_data_version = false,
+        report_data_versions = false,
+        unordered = None,
+        chunk_aware = false,
+        compression = Compression::Auto,
+        flavor = Flavor::Java,
+        nameless_root = false,
+        strict_root = false,
+        float_mode = FloatModeName::Bitwise,
+        abs_tol = 0.0,
+        rel_tol = 0.0,
+        threads = Some(1),
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn compare_many<'py>(
+        py: Python<'py>,
+        pairs: &Bound<'_, PyAny>,
+        exclude_last_update: bool,
+        ignore: Option<&Bound<'_, PyAny>>,
+        ignore_data_version: bool,
+        report_data_versions: bool,
+        unordered: Option<&Bound<'_, PyAny>>,
+        chunk_aware: bool,
+        compression: Compression,
+        flavor: Flavor,
+        nameless_root: bool,
+        strict_root: bool,
+        float_mode: FloatModeName,
+        abs_tol: f64,
+        rel_tol: f64,
+        threads: Option<usize>,
+    ) -> PyResult<Vec<Bound<'py, PyAny>>> {
+        let threads = thread_count(threads)?;
+        let float_mode = FloatMode::new(float_mode, abs_tol, rel_tol)?;
+        let parse = ParseOptions::new(compression, flavor, nameless_root, strict_root);
+        let options = compare_options(
+            ignore,
+            exclude_last_update,
+            ignore_data_version,
+            unordered,
+            chunk_aware,
+            parse,
+            float_mode,
+        )?;
+        let pairs = pairs
+            .try_iter()?
+            .map(|pair| pair?.extract::<(PyBackedBytes, PyBackedBytes)>())
+            .collect::<PyResult<Vec<_>>>()?;
+        let results = py
+            .detach(|| do_compare_many(&pairs, &options, threads, report_data_versions))
+            .map_err(|err| add_side_note(py, err))?;
+        results
+            .into_iter()
+            .map(|(equal, data_versions)| {
+                with_data_versions(py, equal, data_versions, report_data_versions)
+            })
+            .collect()
+    }
 
     #[pyfunction]
-    #[pyo3(signature = (left, right, exclude_last_update = false))]
-    fn compare(
+    #[pyo3(signature = (
+        left,
+        right,
+        exclude_last_update = false,
+        *,
+        ignore = None,
+        ignore_data_version = false,
+        report_data_versions = false,
+        unordered = None,
+        chunk_aware = false,
+        compression = Compression::Auto,
+        flavor = Flavor::Java,
+        nameless_root = false,
+        strict_root = false,
+        float_mode = FloatModeName::Bitwise,
+        abs_tol = 0.0,
+        rel_tol = 0.0,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn diff<'py>(
+        py: Python<'py>,
+        left: &[u8],
+        right: &[u8],
+        exclude_last_update: bool,
+        ignore: Option<&Bound<'_, PyAny>>,
+        ignore_data_version: bool,
+        report_data_versions: bool,
+        unordered: Option<&Bound<'_, PyAny>>,
+        chunk_aware: bool,
+        compression: Compression,
+        flavor: Flavor,
+        nameless_root: bool,
+        strict_root: bool,
+        float_mode: FloatModeName,
+        abs_tol: f64,
+        rel_tol: f64,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let float_mode = FloatMode::new(float_mode, abs_tol, rel_tol)?;
+        let parse = ParseOptions::new(compression, flavor, nameless_root, strict_root);
+        let options = compare_options(
+            ignore,
+            exclude_last_update,
+            ignore_data_version,
+            unordered,
+            chunk_aware,
+            parse,
+            float_mode,
+        )?;
+        let (diffs, data_versions) = py
+            .detach(|| do_diff(left, right, &options))
+            .map_err(|err| add_side_note(py, err))?;
+        let diffs: Vec<Difference> = diffs.into_iter().map(|diff| diff.into_python(py)).collect();
+        with_data_versions(py, diffs, data_versions, report_data_versions)
+    }
+
+    #[pyfunction]
+    #[pyo3(signature = (left, right, *, compression = Compression::Auto))]
+    fn diff_blocks<'py>(
+        py: Python<'py>,
+        left: &[u8],
+        right: &[u8],
+        compression: Compression,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let options = ParseOptions::new(compression, Flavor::Java, false, false);
+        py.detach(|| -> SideResult<_> {
+            let (left, right) = both(left, right, |data| decompress(data, options.compression))?;
+            let (left_tree, right_tree) = both(&left, &right, |data| load_nbt_raw(data, &options))?;
+            let (left_sections, right_sections) = both(&left_tree, &right_tree, block_sections)?;
+            let diffs = diff_sections(&left_sections, &right_sections);
+            // the differences borrow from the trees, so they are converted before returning
+            Ok(Python::attach(|py| {
+                sections_to_python(py, diffs, &left, &right).map(Bound::unbind)
+            }))
+        })
+        .map_err(|err| add_side_note(py, err))?
+        .map(|diffs| diffs.into_bound(py))
+    }
+
+    #[pyfunction]
+    #[pyo3(signature = (
+        data,
+        *,
+        compression = Compression::Auto,
+        flavor = Flavor::Java,
+        nameless_root = false,
+        strict_root = false,
+    ))]
+    fn data_version(
+        py: Python<'_>,
+        data: &[u8],
+        compression: Compression,
+        flavor: Flavor,
+        nameless_root: bool,
+        strict_root: bool,
+    ) -> PyResult<Option<i32>> {
+        let options = ParseOptions::new(compression, flavor, nameless_root, strict_root);
+        py.detach(|| -> Result<_, Error> {
+            let data = decompress(data, compression)?;
+            // only a full parse reports where a buffer is malformed
+            match stream::data_version(&data, &options) {
+                Ok(data_version) => Ok(data_version),
+                Err(_) => Ok(chunk::data_version(&load_nbt_raw(&data, &options)?)),
+            }
+        })
+        .map_err(|e| e.into_pyerr(py))
+    }
+
+    #[pyfunction]
+    #[pyo3(signature = (
+        left,
+        right,
+        exclude_last_update = false,
+        *,
+        ignore = None,
+        ignore_data_version = false,
+        unordered = None,
+        chunk_aware = false,
+        left_external = None,
+        right_external = None,
+        region_pos = (0, 0),
+        float_mode = FloatModeName::Bitwise,
+        abs_tol = 0.0,
+        rel_tol = 0.0,
+        threads = Some(1),
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn compare_region(
         py: Python<'_>,
         left: &[u8],
         right: &[u8],
         exclude_last_update: bool,
-    ) -> PyResult<bool> {
-        py.detach(|| do_compare(left, right, exclude_last_update))
-            .map_err(|(e, side)| {
-                e.add_note(py, format!("Occurred while parsing {side}"))
-                    .unwrap();
-                e
+        ignore: Option<&Bound<'_, PyAny>>,
+        ignore_data_version: bool,
+        unordered: Option<&Bound<'_, PyAny>>,
+        chunk_aware: bool,
+        left_external: Option<&Bound<'_, PyAny>>,
+        right_external: Option<&Bound<'_, PyAny>>,
+        region_pos: (i32, i32),
+        float_mode: FloatModeName,
+        abs_tol: f64,
+        rel_tol: f64,
+        threads: Option<usize>,
+    ) -> PyResult<RegionComparison> {
+        let threads = thread_count(threads)?;
+        let float_mode = FloatMode::new(float_mode, abs_tol, rel_tol)?;
+        // region files only exist in java edition and specify compression per chunk
+        let parse = ParseOptions::new(Compression::None, Flavor::Java, false, false);
+        let options = compare_options(
+            ignore,
+            exclude_last_update,
+            ignore_data_version,
+            unordered,
+            chunk_aware,
+            parse,
+            float_mode,
+        )?;
+        let left_err = |e: PyErr| add_side_note(py, (e.into(), "left".into()));
+        let right_err = |e: PyErr| add_side_note(py, (e.into(), "right".into()));
+        let mut left = Region::new(left, region_pos).map_err(left_err)?;
+        let mut right = Region::new(right, region_pos).map_err(right_err)?;
+        left.resolve_external(left_external, &right)
+            .map_err(left_err)?;
+        right
+            .resolve_external(right_external, &left)
+            .map_err(right_err)?;
+        py.detach(|| compare_regions(&left, &right, &options, threads))
+            .map_err(|err| add_side_note(py, err))
+    }
+
+    #[pyfunction]
+    #[pyo3(signature = (
+        data,
+        *,
+        compression = Compression::Auto,
+        flavor = Flavor::Java,
+        nameless_root = false,
+        strict_root = false,
+        preserve_types = false,
+    ))]
+    fn loads<'py>(
+        py: Python<'py>,
+        data: &[u8],
+        compression: Compression,
+        flavor: Flavor,
+        nameless_root: bool,
+        strict_root: bool,
+        preserve_types: bool,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let options = ParseOptions::new(compression, flavor, nameless_root, strict_root);
+        let data = py.detach(|| decompress(data, compression))?;
+        let tree = py
+            .detach(|| load_nbt_raw(&data, &options))
+            .map_err(|e| e.into_pyerr(py))?;
+        to_python(py, &tree, &data, flavor, preserve_types)
+    }
+
+    #[pyfunction]
+    #[pyo3(signature = (obj, root_name = Some(""), *, flavor = Flavor::Java))]
+    fn dumps<'py>(
+        obj: &Bound<'py, PyAny>,
+        root_name: Option<&str>,
+        flavor: Flavor,
+    ) -> PyResult<Bound<'py, PyBytes>> {
+        Ok(PyBytes::new(
+            obj.py(),
+            &from_python(obj, root_name, flavor)?,
+        ))
+    }
+
+    #[pyfunction]
+    #[pyo3(signature = (
+        data,
+        pretty = true,
+        *,
+        compression = Compression::Auto,
+        flavor = Flavor::Java,
+        nameless_root = false,
+        strict_root = false,
+    ))]
+    fn to_snbt(
+        py: Python<'_>,
+        data: &[u8],
+        pretty: bool,
+        compression: Compression,
+        flavor: Flavor,
+        nameless_root: bool,
+        strict_root: bool,
+    ) -> PyResult<String> {
+        let options = ParseOptions::new(compression, flavor, nameless_root, strict_root);
+        py.detach(|| -> Result<_, Error> {
+            let data = decompress(data, compression)?;
+            let tree = load_nbt_raw(&data, &options)?;
+            snbt::to_snbt(&tree, &data, flavor, pretty)
+        })
+        .map_err(|e| e.into_pyerr(py))
+    }
+
+    #[pyfunction]
+    #[pyo3(signature = (text, root_name = Some(""), *, flavor = Flavor::Java))]
+    fn from_snbt<'py>(
+        py: Python<'py>,
+        text: &str,
+        root_name: Option<&str>,
+        flavor: Flavor,
+    ) -> PyResult<Bound<'py, PyBytes>> {
+        let data = py.detach(|| snbt::from_snbt(text, root_name, flavor))?;
+        Ok(PyBytes::new(py, &data))
+    }
+
+    #[pyfunction]
+    #[pyo3(signature = (
+        data,
+        exclude_last_update = false,
+        *,
+        ignore = None,
+        ignore_data_version = false,
+        unordered = None,
+        chunk_aware = false,
+        compression = Compression::Auto,
+        flavor = Flavor::Java,
+        nameless_root = false,
+        strict_root = false,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn fingerprint<'py>(
+        py: Python<'py>,
+        data: &[u8],
+        exclude_last_update: bool,
+        ignore: Option<&Bound<'_, PyAny>>,
+        ignore_data_version: bool,
+        unordered: Option<&Bound<'_, PyAny>>,
+        chunk_aware: bool,
+        compression: Compression,
+        flavor: Flavor,
+        nameless_root: bool,
+        strict_root: bool,
+    ) -> PyResult<Bound<'py, PyBytes>> {
+        let parse = ParseOptions::new(compression, flavor, nameless_root, strict_root);
+        let options = compare_options(
+            ignore,
+            exclude_last_update,
+            ignore_data_version,
+            unordered,
+            chunk_aware,
+            parse,
+            FloatMode::Bitwise,
+        )?;
+        let digest = py
+            .detach(|| do_fingerprint(data, &options))
+            .map_err(|e| e.into_pyerr(py))?;
+        Ok(PyBytes::new(py, &digest))
+    }
+
+    #[pyfunction]
+    #[pyo3(signature = (reset = false))]
+    fn compare_stats(py: Python<'_>, reset: bool) -> PyResult<Bound<'_, PyDict>> {
+        stats::snapshot(reset).into_py_dict(py)
+    }
+
+    /// Build the options from the arguments shared by all comparison functions
+    fn compare_options(
+        ignore: Option<&Bound<'_, PyAny>>,
+        exclude_last_update: bool,
+        ignore_data_version: bool,
+        unordered: Option<&Bound<'_, PyAny>>,
+        chunk_aware: bool,
+        parse: ParseOptions,
+        float_mode: FloatMode,
+    ) -> PyResult<Options> {
+        Ok(Options {
+            ignore: extract_patterns(ignore, exclude_last_update, ignore_data_version)?,
+            rules: extract_rules(unordered, chunk_aware, parse.flavor)?,
+            parse,
+            float_mode,
+        })
+    }
+
+    /// Return `result` as is, or paired with the `DataVersion` of both sides if they should be reported
+    fn with_data_versions<'py>(
+        py: Python<'py>,
+        result: impl IntoPyObject<'py>,
+        [left, right]: [Option<i32>; 2],
+        report_data_versions: bool,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        match report_data_versions {
+            true => (result, (left, right)).into_bound_py_any(py),
+            false => result.into_bound_py_any(py),
+        }
+    }
+
+    fn add_side_note(py: Python<'_>, (e, side): (Error, Cow<'static, str>)) -> PyErr {
+        let e = e.into_pyerr(py);
+        e.add_note(py, format!("Occurred while parsing {side}"))
+            .unwrap();
+        e
+    }
+}
+
+/// An error together with the side of the comparison it occurred on
+type SideResult<T> = Result<T, (Error, Cow<'static, str>)>;
+
+/// Apply `f` to both sides, remembering which one failed
+fn both<'a, S: ?Sized, T, E: Into<Error>>(
+    left: &'a S,
+    right: &'a S,
+    f: impl Fn(&'a S) -> Result<T, E>,
+) -> SideResult<(T, T)> {
+    Ok((
+        f(left).map_err(|e| (e.into(), "left".into()))?,
+        f(right).map_err(|e| (e.into(), "right".into()))?,
+    ))
+}
+
+/// Apply `f` to `0..len` on up to `threads` threads, keeping the results in order.
+/// Remaining work is skipped once an error occurred.
+fn parallel_map<R: Send, E: Send>(
+    len: usize,
+    threads: usize,
+    f: impl Fn(usize) -> Result<R, E> + Sync,
+) -> Result<Vec<R>, E> {
+    if threads <= 1 || len <= 1 {
+        return (0..len).map(f).collect();
+    }
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let mut results: Vec<(usize, Result<R, E>)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads.min(len))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = Vec::new();
+                    while !failed.load(Ordering::Relaxed) {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        if index >= len {
+                            break;
+                        }
+                        let result = f(index);
+                        if result.is_err() {
+                            failed.store(true, Ordering::Relaxed);
+                        }
+                        results.push((index, result));
+                    }
+                    results
+                })
             })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap_or_else(|e| panic::resume_unwind(e)))
+            .collect()
+    });
+    results.sort_unstable_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Resolve the `threads` argument, using all available cores for `None`
+fn thread_count(threads: Option<usize>) -> PyResult<usize> {
+    match threads {
+        Some(0) => Err(PyValueError::new_err("threads must be at least 1")),
+        Some(threads) => Ok(threads),
+        None => Ok(thread::available_parallelism().map_or(1, NonZeroUsize::get)),
     }
 }
 
+/// How a buffer is turned into a tree
+#[derive(Clone, Copy)]
+struct ParseOptions {
+    compression: Compression,
+    flavor: Flavor,
+    nameless_root: bool,
+    strict_root: bool,
+}
+
+impl ParseOptions {
+    fn new(
+        compression: Compression,
+        flavor: Flavor,
+        nameless_root: bool,
+        strict_root: bool,
+    ) -> Self {
+        ParseOptions {
+            compression,
+            flavor,
+            nameless_root,
+            strict_root,
+        }
+    }
+}
+
+/// Everything that influences how two buffers are parsed and compared
+struct Options {
+    ignore: Vec<PathPattern>,
+    rules: Rules,
+    parse: ParseOptions,
+    float_mode: FloatMode,
+}
+
+impl Options {
+    fn equality(&self) -> Equality<'_> {
+        Equality {
+            floats: FloatCompare::new(self.float_mode, self.parse.flavor),
+            rules: &self.rules,
+            data_versions: [None, None],
+        }
+    }
+
+    /// Whether byte-identical buffers are always equal, which NaN prevents unless floats are compared bitwise
+    fn identical_eq(&self) -> bool {
+        matches!(self.float_mode, FloatMode::Bitwise)
+    }
+}
+
+/// A pruned tree and the `DataVersion` of its root
+type Loaded<'a> = (RawCompound<'a>, Option<i32>);
+
+/// Parse and prune a buffer. The `DataVersion` is read first,
+/// because chunk sections are decoded by it even if it is ignored.
+fn load<'a>(data: &'a [u8], options: &Options) -> ParseResult<Loaded<'a>> {
+    let mut tree = load_nbt_raw(data, &options.parse)?;
+    let data_version = chunk::data_version(&tree);
+    ignore::prune(&mut tree, &Matcher::new(&options.ignore));
+    Ok((tree, data_version))
+}
+
+fn load_pair<'a>(
+    left: &'a [u8],
+    right: &'a [u8],
+    options: &Options,
+) -> SideResult<(Loaded<'a>, Loaded<'a>)> {
+    both(left, right, |data| load(data, options))
+}
+
+/// Compare two buffers, also returning the `DataVersion` of both roots if `report_data_versions` is set
 fn do_compare(
     left: &[u8],
     right: &[u8],
-    exclude_last_update: bool,
-) -> Result<bool, (PyErr, &'static str)> {
-    let left = load_nbt_raw(left).map_err(|e| (e, "left"))?;
-    let right = load_nbt_raw(right).map_err(|e| (e, "right"))?;
-    match (exclude_last_update, left, right) {
-        (false, left, right) => Ok(left == right),
-        (true, RawCompound::Map(mut left), RawCompound::Map(mut right)) => {
-            let key = b"LastUpdate".as_slice();
-            left.remove(key);
-            right.remove(key);
-            Ok(left == right)
-        }
-        _ => unreachable!(),
+    options: &Options,
+    report_data_versions: bool,
+) -> SideResult<(bool, [Option<i32>; 2])> {
+    // identical input decompresses to identical data, but its root can only be checked once decompressed
+    if left == right
+        && options.identical_eq()
+        && !report_data_versions
+        && !options.parse.strict_root
+    {
+        stats::record(stats::Path::Identical);
+        return Ok((true, [None, None]));
+    }
+    let (left, right) = both(left, right, |data| {
+        decompress(data, options.parse.compression)
+    })?;
+    compare_decompressed(&left, &right, options, report_data_versions)
+}
+
+/// Compare without building trees if possible, parsing both sides only to report errors.
+/// Byte-identical buffers are equal no matter which tags are ignored, as long as their root is allowed
+/// and floats are compared bitwise.
+///
+/// Also returns the `DataVersion` of both roots. Identical buffers can only have the same one,
+/// so it is not read for them unless `report_data_versions` is set.
+fn compare_decompressed(
+    left: &[u8],
+    right: &[u8],
+    options: &Options,
+    report_data_versions: bool,
+) -> SideResult<(bool, [Option<i32>; 2])> {
+    if left == right
+        && options.identical_eq()
+        && !report_data_versions
+        && root_allowed(left, &options.parse)
+    {
+        stats::record(stats::Path::Identical);
+        return Ok((true, [None, None]));
+    }
+    // chunk sections can only be decoded once the DataVersion of the root is known
+    if !options.rules.is_chunk_aware()
+        && let Some(compared) = stream::stream_compare(left, right, options)
+    {
+        stats::record(stats::Path::Streamed);
+        return Ok(compared);
     }
+    stats::record(stats::Path::Parsed);
+    let ((left, left_version), (right, right_version)) = load_pair(left, right, options)?;
+    let data_versions = [left_version, right_version];
+    let equal = options
+        .equality()
+        .with_data_versions(data_versions)
+        .trees_eq(&left, &right, &options.rules.matcher());
+    Ok((equal, data_versions))
+}
+
+/// Whether the root tag is a compound if `strict_root` requires it
+fn root_allowed(data: &[u8], options: &ParseOptions) -> bool {
+    let data = match options.flavor {
+        Flavor::Bedrock => strip_bedrock_header(data),
+        Flavor::Java | Flavor::BedrockNetwork => data,
+    };
+    !options.strict_root || data.first() == Some(&10)
+}
+
+/// Compare all pairs, naming the pair in the error
+fn do_compare_many(
+    pairs: &[(PyBackedBytes, PyBackedBytes)],
+    options: &Options,
+    threads: usize,
+    report_data_versions: bool,
+) -> SideResult<Vec<(bool, [Option<i32>; 2])>> {
+    parallel_map(pairs.len(), threads, |index| {
+        let (left, right) = &pairs[index];
+        do_compare(left, right, options, report_data_versions)
+            .map_err(|(e, side)| (e, format!("{side} of pair {index}").into()))
+    })
+}
+
+/// Diff two buffers, also returning the `DataVersion` of both roots
+fn do_diff(
+    left: &[u8],
+    right: &[u8],
+    options: &Options,
+) -> SideResult<(Vec<diff::RawDifference>, [Option<i32>; 2])> {
+    let (left, right) = both(left, right, |data| {
+        decompress(data, options.parse.compression)
+    })?;
+    let ((left, left_version), (right, right_version)) = load_pair(&left, &right, options)?;
+    let data_versions = [left_version, right_version];
+    let equality = options.equality().with_data_versions(data_versions);
+    Ok((diff::diff_raw(&left, &right, equality), data_versions))
+}
+
+fn do_fingerprint(data: &[u8], options: &Options) -> Result<[u8; 32], Error> {
+    let data = decompress(data, options.parse.compression)?;
+    let (tree, data_version) = load(&data, options)?;
+    Ok(fingerprint::fingerprint(
+        &tree,
+        &options.rules,
+        data_version,
+    ))
 }
@@ -0,0 +1,140 @@
+use crate::error::ParseError;
+use crate::path::PathSegment;
+use crate::{
+    BigEndian, Encoding, Flavor, LittleEndian, NetworkLittleEndian, Number, RawCompound, numbers,
+};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict, PyFloat, PyList, PyString};
+use std::marker::PhantomData;
+
+/// Convert a parsed tree into Python objects. `data` is the buffer the tree was parsed from.
+///
+/// With `preserve_types`, numbers are wrapped in the classes from `nbtcompare.tags`.
+pub(crate) fn to_python<'py>(
+    py: Python<'py>,
+    tree: &RawCompound,
+    data: &[u8],
+    flavor: Flavor,
+    preserve_types: bool,
+) -> PyResult<Bound<'py, PyAny>> {
+    let wrappers = match preserve_types {
+        true => {
+            let tags = py.import("nbtcompare.tags")?;
+            ["Byte", "Short", "Int", "Long", "Float", "Double"]
+                .into_iter()
+                .map(|name| tags.getattr(name))
+                .collect::<PyResult<_>>()?
+        }
+        false => Vec::new(),
+    };
+    match flavor {
+        Flavor::Java => Converter::<BigEndian>::new(py, data, wrappers).convert(tree),
+        Flavor::Bedrock => Converter::<LittleEndian>::new(py, data, wrappers).convert(tree),
+        Flavor::BedrockNetwork => {
+            Converter::<NetworkLittleEndian>::new(py, data, wrappers).convert(tree)
+        }
+    }
+}
+
+struct Converter<'py, 'a, E> {
+    py: Python<'py>,
+    data: &'a [u8],
+    /// Wrapper classes indexed by tag id - 1, empty if types are not preserved
+    wrappers: Vec<Bound<'py, PyAny>>,
+    path: Vec<PathSegment<'a>>,
+    encoding: PhantomData<E>,
+}
+
+impl<'py, 'a, E: Encoding> Converter<'py, 'a, E> {
+    fn new(py: Python<'py>, data: &'a [u8], wrappers: Vec<Bound<'py, PyAny>>) -> Self {
+        Converter {
+            py,
+            data,
+            wrappers,
+            path: Vec::new(),
+            encoding: PhantomData,
+        }
+    }
+
+    fn convert(&mut self, tree: &RawCompound<'a>) -> PyResult<Bound<'py, PyAny>> {
+        let py = self.py;
+        Ok(match tree {
+            RawCompound::Mem(tag_id @ 1..=6, num) => {
+                self.number(E::decode_number(num, *tag_id), *tag_id)?
+            }
+            RawCompound::Mem(7, array) => PyBytes::new(py, array).into_any(),
+            RawCompound::Mem(8, string) => self.string(string)?.into_any(),
+            RawCompound::Mem(11, array) => self.array(array, 3)?,
+            RawCompound::Mem(12, array) => self.array(array, 4)?,
+            RawCompound::Mem(tag_id, _) => unreachable!("tag {tag_id} is not stored in memory"),
+            RawCompound::PackedList(tag_id, list) => PyList::new(
+                py,
+                numbers::<E>(list, *tag_id)
+                    .map(|num| self.number(num, *tag_id))
+                    .collect::<PyResult<Vec<_>>>()?,
+            )?
+            .into_any(),
+            RawCompound::List(_, list) => {
+                let res = PyList::empty(py);
+                for (index, element) in list.iter().enumerate() {
+                    self.path.push(PathSegment::Index(index));
+                    res.append(self.convert(element)?)?;
+                    self.path.pop();
+                }
+                res.into_any()
+            }
+            RawCompound::Map(_, map) => {
+                // compounds are unordered, sort them for a stable result
+                let mut entries: Vec<_> = map.iter().collect();
+                entries.sort_unstable_by_key(|(key, _)| *key);
+                let res = PyDict::new(py);
+                for (key, value) in entries {
+                    let key_obj = self.string(key)?;
+                    self.path.push(PathSegment::Key(key));
+                    res.set_item(key_obj, self.convert(value)?)?;
+                    self.path.pop();
+                }
+                res.into_any()
+            }
+        })
+    }
+
+    fn number(&self, num: Number, tag_id: u8) -> PyResult<Bound<'py, PyAny>> {
+        let obj = match num {
+            Number::Int(int) => int.into_pyobject(self.py)?.into_any(),
+            Number::Float(float) => PyFloat::new(self.py, float).into_any(),
+        };
+        match self.wrappers.get(usize::from(tag_id) - 1) {
+            Some(wrapper) => wrapper.call1((obj,)),
+            None => Ok(obj),
+        }
+    }
+
+    fn string(&self, string: &[u8]) -> PyResult<Bound<'py, PyString>> {
+        match E::decode_string(string) {
+            Some(string) => Ok(PyString::new(self.py, &string)),
+            None => {
+                Err(ParseError::invalid_string(string, self.data, &self.path).into_pyerr(self.py))
+            }
+        }
+    }
+
+    /// Convert an int or long array into an `array.array` of the same width
+    fn array(&self, array: &[u8], element_id: u8) -> PyResult<Bound<'py, PyAny>> {
+        let mut native = Vec::with_capacity(array.len());
+        for num in numbers::<E>(array, element_id) {
+            let Number::Int(int) = num else {
+                unreachable!("arrays only contain integers")
+            };
+            match element_id {
+                3 => native.extend_from_slice(&(int as i32).to_ne_bytes()),
+                _ => native.extend_from_slice(&int.to_ne_bytes()),
+            }
+        }
+        let type_code = if element_id == 3 { "i" } else { "q" };
+        self.py
+            .import("array")?
+            .getattr("array")?
+            .call1((type_code, PyBytes::new(self.py, &native)))
+    }
+}
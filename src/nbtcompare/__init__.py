@@ -2,4 +2,20 @@
 
 A pure python reference implementation of this package is available at https://github.com/Birnendampf/MineDelta.
 """
-from nbtcompare._core import compare
+from nbtcompare._core import (
+    Difference,
+    NBTParseError,
+    RegionComparison,
+    compare,
+    compare_many,
+    compare_stats,
+    compare_region,
+    data_version,
+    diff,
+    diff_blocks,
+    dumps,
+    fingerprint,
+    from_snbt,
+    loads,
+    to_snbt,
+)
@@ -1 +1,363 @@
-def compare(left: bytes, right: bytes, exclude_last_update: bool = False) -> bool: ...
+from collections.abc import Callable, Iterable, Mapping
+from os import PathLike
+from typing import Any, Literal, TypeAlias, final, overload
+
+Compression: TypeAlias = Literal["auto", "none", "gzip", "zlib"]
+Flavor: TypeAlias = Literal["java", "bedrock", "bedrock_network"]
+FloatMode: TypeAlias = Literal["bitwise", "ieee", "tolerance"]
+DataVersions: TypeAlias = tuple[int | None, int | None]
+
+@overload
+def compare(
+    left: bytes,
+    right: bytes,
+    exclude_last_update: bool = False,
+    *,
+    ignore: Iterable[str] | None = None,
+    ignore_data_version: bool = False,
+    report_data_versions: Literal[False] = False,
+    unordered: Iterable[str] | Mapping[str, str | None] | None = None,
+    chunk_aware: bool = False,
+    compression: Compression = "auto",
+    flavor: Flavor = "java",
+    nameless_root: bool = False,
+    strict_root: bool = False,
+    float_mode: FloatMode = "bitwise",
+    abs_tol: float = 0.0,
+    rel_tol: float = 0.0,
+) -> bool:
+    """Compare two NBT buffers.
+
+    Gzip and zlib compressed input is detected and decompressed automatically,
+    ``compression`` can be used to override the detection.
+
+    ``flavor`` selects between big-endian Java NBT, little-endian Bedrock NBT
+    and Bedrock's network NBT, which uses VarInts for lengths, ints and longs.
+    The 8 byte header of Bedrock's level.dat is skipped automatically.
+    ``nameless_root`` accepts network NBT (Java 1.20.2+), where the root tag has no name.
+    The root tag may be of any type unless ``strict_root`` is set, which requires a compound.
+
+    ``ignore`` takes path patterns like ``sections[*].SkyLight`` or ``**.UUID`` that are skipped during comparison.
+    ``*`` matches any key, ``[*]`` any list index and ``**`` any number of segments.
+    ``exclude_last_update`` is a shorthand for ignoring ``LastUpdate``.
+    ``ignore_data_version`` ignores the ``DataVersion`` of the root, which changes whenever a world is upgraded.
+    Chunk sections are still decoded by the ``DataVersion`` of their own side, see ``data_version``.
+    ``report_data_versions`` returns ``(result, (left_version, right_version))`` instead of the bare result,
+    so an ignored change is not lost. Either version is ``None`` if its root has no ``DataVersion``.
+
+    ``unordered`` takes patterns of lists like ``Entities`` or ``**.Inventory`` whose order does not matter,
+    so their elements are compared as a multiset. Mapping a pattern to a key like ``UUID`` or ``Slot`` pairs up
+    compound elements by that key, so ``diff`` reports changes within them instead of a removed and an added
+    element. Elements are reported at their index on the left, added ones at their index on the right.
+
+    ``chunk_aware`` decodes the ``block_states`` and ``biomes`` of each chunk section (1.18+) and compares their
+    entries, so reordered palettes and repacked ``data`` arrays do not count as changes. Older chunks are decoded
+    from the ``Palette`` and ``BlockStates`` of ``Level.Sections``, packed according to the ``DataVersion``, or from
+    the numeric ``Blocks``, ``Data`` and ``Add`` arrays before 1.13. ``diff`` reports a changed container as a
+    whole. Containers that can not be decoded are compared like any other compound.
+    Chunk aware comparisons always parse both buffers.
+
+    ``float_mode`` controls how floats and doubles are compared, including those in lists.
+    ``"bitwise"`` compares their encoding, ``"ieee"`` their value, so ``-0.0 == 0.0`` but NaN is never equal.
+    ``"tolerance"`` accepts values within ``abs_tol`` or ``rel_tol`` of each other, like ``math.isclose``.
+    """
+
+@overload
+def compare(
+    left: bytes,
+    right: bytes,
+    exclude_last_update: bool = False,
+    *,
+    ignore: Iterable[str] | None = None,
+    ignore_data_version: bool = False,
+    report_data_versions: Literal[True],
+    unordered: Iterable[str] | Mapping[str, str | None] | None = None,
+    chunk_aware: bool = False,
+    compression: Compression = "auto",
+    flavor: Flavor = "java",
+    nameless_root: bool = False,
+    strict_root: bool = False,
+    float_mode: FloatMode = "bitwise",
+    abs_tol: float = 0.0,
+    rel_tol: float = 0.0,
+) -> tuple[bool, DataVersions]: ...
+
+@overload
+def compare_many(
+    pairs: Iterable[tuple[bytes, bytes]],
+    exclude_last_update: bool = False,
+    *,
+    ignore: Iterable[str] | None = None,
+    ignore_data_version: bool = False,
+    report_data_versions: Literal[False] = False,
+    unordered: Iterable[str] | Mapping[str, str | None] | None = None,
+    chunk_aware: bool = False,
+    compression: Compression = "auto",
+    flavor: Flavor = "java",
+    nameless_root: bool = False,
+    strict_root: bool = False,
+    float_mode: FloatMode = "bitwise",
+    abs_tol: float = 0.0,
+    rel_tol: float = 0.0,
+    threads: int | None = 1,
+) -> list[bool]:
+    """Compare each ``(left, right)`` pair like ``compare`` does, releasing the GIL only once.
+
+    Pairs are distributed over ``threads`` threads, or all available cores if it is ``None``.
+    Results are in the order of ``pairs``, with ``report_data_versions`` each is paired with the ``DataVersion``
+    of both sides.
+    If a buffer is malformed, the error names the index of its pair and no results are returned.
+    """
+
+@overload
+def compare_many(
+    pairs: Iterable[tuple[bytes, bytes]],
+    exclude_last_update: bool = False,
+    *,
+    ignore: Iterable[str] | None = None,
+    ignore_data_version: bool = False,
+    report_data_versions: Literal[True],
+    unordered: Iterable[str] | Mapping[str, str | None] | None = None,
+    chunk_aware: bool = False,
+    compression: Compression = "auto",
+    flavor: Flavor = "java",
+    nameless_root: bool = False,
+    strict_root: bool = False,
+    float_mode: FloatMode = "bitwise",
+    abs_tol: float = 0.0,
+    rel_tol: float = 0.0,
+    threads: int | None = 1,
+) -> list[tuple[bool, DataVersions]]: ...
+
+def compare_stats(reset: bool = False) -> dict[Literal["identical", "streamed", "parsed", "map_fallback"], int]:
+    """Count how comparisons were decided since the module was loaded or the counters were ``reset``.
+
+    ``identical`` counts byte-identical buffers, which are equal without parsing no matter what is ignored,
+    unless ``float_mode`` is not ``"bitwise"``, as NaN is never equal then, or ``report_data_versions`` is set.
+    ``streamed`` counts comparisons decided by walking both buffers in lockstep, ``parsed`` those that needed a
+    full parse because a buffer was malformed or ``chunk_aware`` was set. ``map_fallback`` counts compounds whose
+    key order diverged while streaming. Chunks compared by ``compare_region`` and pairs of ``compare_many`` are
+    counted individually.
+    """
+
+def data_version(
+    data: bytes,
+    *,
+    compression: Compression = "auto",
+    flavor: Flavor = "java",
+    nameless_root: bool = False,
+    strict_root: bool = False,
+) -> int | None:
+    """Return the ``DataVersion`` of the root of a buffer, or ``None`` if it has none.
+
+    The buffer is checked without building a tree, so ``NBTParseError`` is still raised for malformed data.
+    """
+
+@overload
+def diff(
+    left: bytes,
+    right: bytes,
+    exclude_last_update: bool = False,
+    *,
+    ignore: Iterable[str] | None = None,
+    ignore_data_version: bool = False,
+    report_data_versions: Literal[False] = False,
+    unordered: Iterable[str] | Mapping[str, str | None] | None = None,
+    chunk_aware: bool = False,
+    compression: Compression = "auto",
+    flavor: Flavor = "java",
+    nameless_root: bool = False,
+    strict_root: bool = False,
+    float_mode: FloatMode = "bitwise",
+    abs_tol: float = 0.0,
+    rel_tol: float = 0.0,
+) -> list[Difference]: ...
+@overload
+def diff(
+    left: bytes,
+    right: bytes,
+    exclude_last_update: bool = False,
+    *,
+    ignore: Iterable[str] | None = None,
+    ignore_data_version: bool = False,
+    report_data_versions: Literal[True],
+    unordered: Iterable[str] | Mapping[str, str | None] | None = None,
+    chunk_aware: bool = False,
+    compression: Compression = "auto",
+    flavor: Flavor = "java",
+    nameless_root: bool = False,
+    strict_root: bool = False,
+    float_mode: FloatMode = "bitwise",
+    abs_tol: float = 0.0,
+    rel_tol: float = 0.0,
+) -> tuple[list[Difference], DataVersions]: ...
+def diff_blocks(
+    left: bytes, right: bytes, *, compression: Compression = "auto"
+) -> dict[int, list[tuple[int, int, int, Any, Any]]]:
+    """Find the blocks that differ between two versions of a chunk.
+
+    The block states of each section are decoded like ``chunk_aware`` does, so reordered palettes do not show up
+    as changes.
+    The result maps the ``Y`` of each section with changes to a list of ``(x, y, z, old_state, new_state)``,
+    with coordinates from 0 to 15 within the section. States are palette entries converted like ``loads`` does,
+    e.g. ``{"Name": "minecraft:oak_stairs", "Properties": {"facing": "east"}}``, or ``None`` if the section only
+    exists on the other side. Before 1.13 states are ``(id, data)`` tuples of the numeric block id and data value.
+    """
+
+def fingerprint(
+    data: bytes,
+    exclude_last_update: bool = False,
+    *,
+    ignore: Iterable[str] | None = None,
+    ignore_data_version: bool = False,
+    unordered: Iterable[str] | Mapping[str, str | None] | None = None,
+    chunk_aware: bool = False,
+    compression: Compression = "auto",
+    flavor: Flavor = "java",
+    nameless_root: bool = False,
+    strict_root: bool = False,
+) -> bytes:
+    """Compute a 32 byte BLAKE3 digest of an NBT buffer.
+
+    Buffers that ``compare`` considers equal with the same options get the same digest,
+    regardless of the order of compound entries and of the elements of ``unordered`` lists.
+    The digest depends on ``flavor``.
+    """
+
+ExternalResolver: TypeAlias = Callable[[int, int], bytes] | str | PathLike[str]
+
+def compare_region(
+    left: bytes,
+    right: bytes,
+    exclude_last_update: bool = False,
+    *,
+    ignore: Iterable[str] | None = None,
+    ignore_data_version: bool = False,
+    unordered: Iterable[str] | Mapping[str, str | None] | None = None,
+    chunk_aware: bool = False,
+    left_external: ExternalResolver | None = None,
+    right_external: ExternalResolver | None = None,
+    region_pos: tuple[int, int] = (0, 0),
+    float_mode: FloatMode = "bitwise",
+    abs_tol: float = 0.0,
+    rel_tol: float = 0.0,
+    threads: int | None = 1,
+) -> RegionComparison:
+    """Compare all chunks of two region (.mca) files.
+
+    The options apply to each chunk the same way as they do for ``compare``.
+
+    Oversized chunks are stored in separate ``c.X.Z.mcc`` files. To compare them, pass the directory containing them
+    or a callable that receives the chunk coordinates and returns the file content as ``left_external`` and
+    ``right_external``. Chunk coordinates are absolute if ``region_pos`` is set to the coordinates of the region.
+    External files are only read for chunks present in both regions, as added and removed chunks are not parsed.
+
+    Chunks are compared on ``threads`` threads, or all available cores if it is ``None``.
+    """
+
+def loads(
+    data: bytes,
+    *,
+    compression: Compression = "auto",
+    flavor: Flavor = "java",
+    nameless_root: bool = False,
+    strict_root: bool = False,
+    preserve_types: bool = False,
+) -> Any:
+    """Parse an NBT buffer into Python objects.
+
+    Compounds become ``dict`` with sorted keys, lists ``list``, strings ``str`` and numbers ``int`` or ``float``.
+    Byte arrays become ``bytes``, int and long arrays ``array.array`` with type code ``"i"`` and ``"q"``.
+    With ``preserve_types``, numbers are wrapped in the classes from ``nbtcompare.tags`` so their tag type is kept.
+    The name of the root tag is discarded. The other options behave like they do for ``compare``.
+    """
+
+def dumps(obj: Any, root_name: str | None = "", *, flavor: Flavor = "java") -> bytes:
+    """Serialize Python objects to uncompressed NBT, the inverse of ``loads``.
+
+    ``int`` becomes TAG_Int, or TAG_Long if it does not fit, ``float`` TAG_Double and ``bool`` TAG_Byte.
+    The classes from ``nbtcompare.tags`` select a specific numeric type.
+    ``bytes`` and ``bytearray`` become TAG_Byte_Array, ``array.array`` a byte, int or long array depending on its
+    item size. All elements of a list must have the same tag type, except that ``int`` elements become TAG_Long
+    if any element of the list does.
+    The root tag is written without a name if ``root_name`` is ``None``.
+    Lists and dicts may be nested 512 levels deep, like in ``from_snbt``. ``ValueError`` is raised for deeper
+    nesting and for a list or dict that contains itself.
+    """
+
+def to_snbt(
+    data: bytes,
+    pretty: bool = True,
+    *,
+    compression: Compression = "auto",
+    flavor: Flavor = "java",
+    nameless_root: bool = False,
+    strict_root: bool = False,
+) -> str:
+    """Render an NBT buffer as SNBT, e.g. ``{Count:1b,id:"minecraft:stone"}``.
+
+    Keys are sorted so the output is stable. With ``pretty``, compounds and lists are indented over multiple
+    lines, arrays and lists of numbers stay on one line. The other options behave like they do for ``compare``.
+    NaN and infinite floats raise ``ValueError``, as SNBT has no literal for them.
+    """
+
+def from_snbt(text: str, root_name: str | None = "", *, flavor: Flavor = "java") -> bytes:
+    """Parse SNBT like the vanilla ``/data`` command and encode it as uncompressed NBT.
+
+    Unquoted numbers are typed by their suffix (``1b``, ``2s``, ``3L``, ``4.0f``, ``5d``), numbers without one
+    become TAG_Int or TAG_Double if they contain a ``.``, ``true`` and ``false`` become bytes and anything else a
+    string. The root tag is written without a name if ``root_name`` is ``None``.
+    Compounds and lists may be nested 512 levels deep, like in game.
+    """
+
+class NBTParseError(ValueError):
+    """Raised for malformed NBT data.
+
+    ``offset`` is the byte offset in the (decompressed) input, ``path`` the path of the tag being parsed and
+    ``tag_id`` its type, if known.
+    """
+
+    offset: int
+    path: str
+    tag_id: int | None
+
+@final
+class Difference:
+    """A single difference between two NBT trees.
+
+    ``left`` and ``right`` hold the raw payload of the value on either side (``None`` if it is missing).
+    Length prefixes and list headers are stripped, so the tag id is needed to interpret them.
+    """
+
+    @property
+    def path(self) -> str: ...
+    @property
+    def kind(self) -> Literal["added", "removed", "changed", "type-changed"]: ...
+    @property
+    def left_tag(self) -> int | None: ...
+    @property
+    def left(self) -> bytes | None: ...
+    @property
+    def right_tag(self) -> int | None: ...
+    @property
+    def right(self) -> bytes | None: ...
+
+@final
+class RegionComparison:
+    """Result of comparing two region files.
+
+    Chunks are identified by their index ``x + z * 32`` within the region.
+    ``data_versions`` maps chunks present in both regions to their ``(left, right)`` ``DataVersion``
+    if it differs, whether or not it was ignored.
+    """
+
+    @property
+    def added(self) -> list[int]: ...
+    @property
+    def removed(self) -> list[int]: ...
+    @property
+    def changed(self) -> list[int]: ...
+    @property
+    def unchanged(self) -> list[int]: ...
+    @property
+    def data_versions(self) -> dict[int, tuple[int | None, int | None]]: ...
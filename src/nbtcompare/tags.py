@@ -0,0 +1,47 @@
+"""Wrapper types that keep track of the exact NBT type of a number.
+
+:func:`nbtcompare.loads` returns them when called with ``preserve_types=True``.
+Arithmetic on them returns plain ``int`` and ``float`` objects.
+"""
+
+from typing import ClassVar
+
+__all__ = ["Byte", "Double", "Float", "Int", "Long", "Short"]
+
+
+class _Numeric:
+    __slots__ = ()
+    tag_id: ClassVar[int]
+
+    def __repr__(self) -> str:
+        return f"{type(self).__name__}({super().__repr__()})"
+
+
+class Byte(_Numeric, int):
+    __slots__ = ()
+    tag_id = 1
+
+
+class Short(_Numeric, int):
+    __slots__ = ()
+    tag_id = 2
+
+
+class Int(_Numeric, int):
+    __slots__ = ()
+    tag_id = 3
+
+
+class Long(_Numeric, int):
+    __slots__ = ()
+    tag_id = 4
+
+
+class Float(_Numeric, float):
+    __slots__ = ()
+    tag_id = 5
+
+
+class Double(_Numeric, float):
+    __slots__ = ()
+    tag_id = 6
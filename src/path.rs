@@ -0,0 +1,44 @@
+use std::fmt::Write;
+
+#[derive(Clone, Copy)]
+pub(crate) enum PathSegment<'a> {
+    Key(&'a [u8]),
+    Index(usize),
+}
+
+/// Format a path like `sections[3].block_states.palette[0].Name`.
+/// Keys that are empty or contain anything besides `[A-Za-z0-9_+-]` are double-quoted.
+pub(crate) fn format_path(path: &[PathSegment]) -> String {
+    let mut res = String::new();
+    for segment in path {
+        match segment {
+            PathSegment::Key(key) => {
+                if !res.is_empty() {
+                    res.push('.');
+                }
+                push_key(&mut res, &String::from_utf8_lossy(key));
+            }
+            PathSegment::Index(index) => write!(res, "[{index}]").unwrap(),
+        }
+    }
+    res
+}
+
+fn push_key(res: &mut String, key: &str) {
+    if !key.is_empty()
+        && key
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'-' | b'+'))
+    {
+        res.push_str(key);
+        return;
+    }
+    res.push('"');
+    for c in key.chars() {
+        if matches!(c, '"' | '\\') {
+            res.push('\\');
+        }
+        res.push(c);
+    }
+    res.push('"');
+}
@@ -0,0 +1,256 @@
+use crate::compression::{Compression, decompress};
+use crate::error::Error;
+use crate::{Options, SideResult, compare_decompressed, parallel_map};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::pybacked::PyBackedBytes;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+
+const SECTOR_SIZE: usize = 4096;
+const CHUNK_COUNT: usize = 1024;
+
+/// Result of comparing two region files.
+///
+/// Chunks are identified by their index `x + z * 32` within the region.
+#[pyclass(frozen, get_all, module = "nbtcompare._core")]
+pub(crate) struct RegionComparison {
+    added: Vec<usize>,
+    removed: Vec<usize>,
+    changed: Vec<usize>,
+    unchanged: Vec<usize>,
+    /// The `DataVersion` of chunks in both regions, if it differs
+    data_versions: BTreeMap<usize, (Option<i32>, Option<i32>)>,
+}
+
+#[pymethods]
+impl RegionComparison {
+    fn __repr__(&self) -> String {
+        format!(
+            "<RegionComparison added={} removed={} changed={} unchanged={}>",
+            self.added.len(),
+            self.removed.len(),
+            self.changed.len(),
+            self.unchanged.len()
+        )
+    }
+}
+
+/// Where to find oversized chunks that are stored in separate `c.X.Z.mcc` files
+enum External {
+    Missing,
+    Dir(PathBuf),
+    Loaded(HashMap<usize, PyBackedBytes>),
+}
+
+pub(crate) struct Region<'a> {
+    data: &'a [u8],
+    /// Byte offsets of all chunks in the region, 0 if the chunk is not present
+    locations: Vec<usize>,
+    external: External,
+    /// Absolute coordinates of the chunk at index 0
+    origin: (i32, i32),
+}
+
+impl<'a> Region<'a> {
+    /// Parse the region header
+    pub(crate) fn new(data: &'a [u8], (region_x, region_z): (i32, i32)) -> PyResult<Self> {
+        Ok(Region {
+            data,
+            locations: Self::locations(data)?,
+            external: External::Missing,
+            origin: (region_x * 32, region_z * 32),
+        })
+    }
+
+    /// Set where to find external chunks. Chunks from callable resolvers are loaded right away,
+    /// so they don't have to be requested once the GIL is released. Only chunks that `other`
+    /// contains as well are loaded, as the others are never decompressed.
+    pub(crate) fn resolve_external(
+        &mut self,
+        resolver: Option<&Bound<'_, PyAny>>,
+        other: &Region,
+    ) -> PyResult<()> {
+        self.external = match resolver {
+            None => External::Missing,
+            Some(resolver) if resolver.is_callable() => {
+                let mut chunks = HashMap::new();
+                for index in 0..CHUNK_COUNT {
+                    if other.locations[index] != 0 && self.is_external(index)? {
+                        chunks.insert(index, resolver.call1(self.position(index))?.extract()?);
+                    }
+                }
+                External::Loaded(chunks)
+            }
+            Some(resolver) => External::Dir(resolver.extract()?),
+        };
+        Ok(())
+    }
+
+    fn locations(data: &[u8]) -> PyResult<Vec<usize>> {
+        // the game sometimes leaves behind empty region files
+        if data.is_empty() {
+            return Ok(vec![0; CHUNK_COUNT]);
+        }
+        let header = data
+            .get(..SECTOR_SIZE)
+            .ok_or_else(|| PyValueError::new_err("Region header is truncated"))?;
+        Ok(header
+            .chunks_exact(4)
+            .map(|entry| {
+                u32::from_be_bytes([0, entry[0], entry[1], entry[2]]) as usize * SECTOR_SIZE
+            })
+            .collect())
+    }
+
+    fn position(&self, index: usize) -> (i32, i32) {
+        (
+            self.origin.0 + (index % 32) as i32,
+            self.origin.1 + (index / 32) as i32,
+        )
+    }
+
+    fn is_external(&self, index: usize) -> PyResult<bool> {
+        Ok(match self.raw_chunk(index)? {
+            Some((compression, _)) => compression & 0x80 != 0,
+            None => false,
+        })
+    }
+
+    /// The compression type and (compressed) data of a chunk
+    fn raw_chunk(&self, index: usize) -> PyResult<Option<(u8, &'a [u8])>> {
+        let offset = self.locations[index];
+        if offset == 0 {
+            return Ok(None);
+        }
+        let truncated = || PyValueError::new_err("Chunk data is truncated");
+        let (header, data) = self
+            .data
+            .get(offset..)
+            .and_then(<[u8]>::split_first_chunk::<5>)
+            .ok_or_else(truncated)?;
+        let length = u32::from_be_bytes(header[..4].try_into().unwrap()) as usize;
+        let data = data.get(..length.saturating_sub(1)).ok_or_else(truncated)?;
+        Ok(Some((header[4], data)))
+    }
+
+    /// The decompressed data of the chunk at `index`, given its raw chunk
+    fn chunk(
+        &self,
+        index: usize,
+        (compression_id, data): (u8, &'a [u8]),
+    ) -> PyResult<Cow<'_, [u8]>> {
+        let compression = match compression_id & 0x7f {
+            1 => Compression::Gzip,
+            2 => Compression::Zlib,
+            3 => Compression::None,
+            compression => {
+                return Err(PyValueError::new_err(format!(
+                    "Unsupported chunk compression: {compression}"
+                )));
+            }
+        };
+        if compression_id & 0x80 == 0 {
+            return decompress(data, compression);
+        }
+        match &self.external {
+            External::Missing => Err(PyValueError::new_err(
+                "Chunk is stored in an external .mcc file, but no resolver was given",
+            )),
+            External::Loaded(chunks) => decompress(&chunks[&index], compression),
+            External::Dir(dir) => {
+                let (x, z) = self.position(index);
+                let data = fs::read(dir.join(format!("c.{x}.{z}.mcc")))?;
+                Ok(Cow::Owned(decompress(&data, compression)?.into_owned()))
+            }
+        }
+    }
+}
+
+pub(crate) fn compare_regions(
+    left: &Region,
+    right: &Region,
+    options: &Options,
+    threads: usize,
+) -> SideResult<RegionComparison> {
+    let states = parallel_map(CHUNK_COUNT, threads, |index| {
+        compare_chunk(left, right, index, options)
+    })?;
+    let mut res = RegionComparison {
+        added: Vec::new(),
+        removed: Vec::new(),
+        changed: Vec::new(),
+        unchanged: Vec::new(),
+        data_versions: BTreeMap::new(),
+    };
+    for (index, state) in states.into_iter().enumerate() {
+        match state {
+            None => {}
+            Some(ChunkState::Added) => res.added.push(index),
+            Some(ChunkState::Removed) => res.removed.push(index),
+            Some(ChunkState::Present {
+                equal,
+                data_versions,
+            }) => {
+                if data_versions.0 != data_versions.1 {
+                    res.data_versions.insert(index, data_versions);
+                }
+                match equal {
+                    true => res.unchanged.push(index),
+                    false => res.changed.push(index),
+                }
+            }
+        }
+    }
+    Ok(res)
+}
+
+enum ChunkState {
+    Added,
+    Removed,
+    /// The chunk exists in both regions
+    Present {
+        equal: bool,
+        data_versions: (Option<i32>, Option<i32>),
+    },
+}
+
+/// Compare the chunk at `index`, `None` if it is missing on both sides
+fn compare_chunk(
+    left: &Region,
+    right: &Region,
+    index: usize,
+    options: &Options,
+) -> SideResult<Option<ChunkState>> {
+    let left_raw = left.raw_chunk(index).map_err(chunk_err(index, "left"))?;
+    let right_raw = right.raw_chunk(index).map_err(chunk_err(index, "right"))?;
+    // chunks on one side only are not decompressed, so their external files are not needed
+    let (left_raw, right_raw) = match (left_raw, right_raw) {
+        (None, None) => return Ok(None),
+        (Some(_), None) => return Ok(Some(ChunkState::Removed)),
+        (None, Some(_)) => return Ok(Some(ChunkState::Added)),
+        (Some(left_raw), Some(right_raw)) => (left_raw, right_raw),
+    };
+    let left_chunk = left
+        .chunk(index, left_raw)
+        .map_err(chunk_err(index, "left"))?;
+    let right_chunk = right
+        .chunk(index, right_raw)
+        .map_err(chunk_err(index, "right"))?;
+    let (equal, [left_version, right_version]) =
+        compare_decompressed(&left_chunk, &right_chunk, options, false)
+            .map_err(|(e, side)| chunk_err(index, &side)(e))?;
+    Ok(Some(ChunkState::Present {
+        equal,
+        data_versions: (left_version, right_version),
+    }))
+}
+
+fn chunk_err<E: Into<Error>>(
+    index: usize,
+    side: &str,
+) -> impl FnOnce(E) -> (Error, Cow<'static, str>) + '_ {
+    move |e| (e.into(), format!("chunk {index} of {side}").into())
+}
@@ -0,0 +1,63 @@
+use crate::Flavor;
+use crate::chunk::{self, Container};
+use crate::ignore::{Matcher, PathPattern};
+use crate::unordered::extract_unordered;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+/// How the tag at a path is compared
+pub(crate) enum Rule {
+    /// A list whose element order does not matter, with the key that identifies its elements, if any
+    Unordered(Option<Vec<u8>>),
+    /// A paletted container of a chunk section, compared by its decoded entries
+    Paletted(Container),
+}
+
+/// Paths that are compared differently, the first matching pattern wins
+#[derive(Default)]
+pub(crate) struct Rules {
+    patterns: Vec<PathPattern>,
+    rules: Vec<Rule>,
+    chunk_aware: bool,
+}
+
+impl Rules {
+    pub(crate) fn push(&mut self, pattern: PathPattern, rule: Rule) {
+        self.patterns.push(pattern);
+        self.rules.push(rule);
+    }
+
+    /// Whether the containers of chunk sections are decoded
+    pub(crate) fn is_chunk_aware(&self) -> bool {
+        self.chunk_aware
+    }
+
+    pub(crate) fn matcher(&self) -> Matcher<'_> {
+        Matcher::new(&self.patterns)
+    }
+
+    /// The rule for the current path of `matcher`
+    pub(crate) fn rule(&self, matcher: &Matcher) -> Option<&Rule> {
+        matcher.matched().map(|pattern| &self.rules[pattern])
+    }
+}
+
+/// Collect the rules from the `unordered` and `chunk_aware` arguments of the python API
+pub(crate) fn extract_rules(
+    unordered: Option<&Bound<'_, PyAny>>,
+    chunk_aware: bool,
+    flavor: Flavor,
+) -> PyResult<Rules> {
+    let mut rules = Rules::default();
+    extract_unordered(unordered, &mut rules)?;
+    if chunk_aware {
+        if !matches!(flavor, Flavor::Java) {
+            return Err(PyValueError::new_err(
+                "chunk_aware requires flavor=\"java\"",
+            ));
+        }
+        rules.chunk_aware = true;
+        chunk::push_rules(&mut rules);
+    }
+    Ok(rules)
+}
@@ -0,0 +1,633 @@
+use crate::error::{Error, ParseError, ParseResult};
+use crate::path::{PathSegment, format_path};
+use crate::writer::Writer;
+use crate::{
+    BigEndian, Encoding, Flavor, LittleEndian, NetworkLittleEndian, Number, RawCompound, numbers,
+};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::marker::PhantomData;
+
+/// Render a parsed tree as SNBT. `data` is the buffer the tree was parsed from.
+///
+/// Keys are sorted so the output is stable. With `pretty`, compounds and lists are
+/// spread over multiple lines, while arrays and lists of numbers stay on one line.
+/// NaN and infinite floats raise, as SNBT has no literal for them.
+pub(crate) fn to_snbt(
+    tree: &RawCompound,
+    data: &[u8],
+    flavor: Flavor,
+    pretty: bool,
+) -> Result<String, Error> {
+    match flavor {
+        Flavor::Java => Printer::<BigEndian>::new(data, pretty).print(tree),
+        Flavor::Bedrock => Printer::<LittleEndian>::new(data, pretty).print(tree),
+        Flavor::BedrockNetwork => Printer::<NetworkLittleEndian>::new(data, pretty).print(tree),
+    }
+}
+
+struct Printer<'a, E> {
+    data: &'a [u8],
+    pretty: bool,
+    depth: usize,
+    path: Vec<PathSegment<'a>>,
+    out: String,
+    encoding: PhantomData<E>,
+}
+
+impl<'a, E: Encoding> Printer<'a, E> {
+    fn new(data: &'a [u8], pretty: bool) -> Self {
+        Printer {
+            data,
+            pretty,
+            depth: 0,
+            path: Vec::new(),
+            out: String::new(),
+            encoding: PhantomData,
+        }
+    }
+
+    fn print(mut self, tree: &RawCompound<'a>) -> Result<String, Error> {
+        self.value(tree)?;
+        Ok(self.out)
+    }
+
+    fn value(&mut self, tree: &RawCompound<'a>) -> Result<(), Error> {
+        match tree {
+            RawCompound::Mem(tag_id @ 1..=6, num) => {
+                self.number(E::decode_number(num, *tag_id), *tag_id, None)?
+            }
+            RawCompound::Mem(7, array) => self.numbers(
+                "B;",
+                array.iter().map(|byte| Number::Int((*byte as i8).into())),
+                1,
+            )?,
+            RawCompound::Mem(8, string) => {
+                let string = self.string(string)?;
+                write_quoted(&mut self.out, &string);
+            }
+            RawCompound::Mem(11, array) => self.numbers("I;", numbers::<E>(array, 3), 3)?,
+            RawCompound::Mem(12, array) => self.numbers("L;", numbers::<E>(array, 4), 4)?,
+            RawCompound::Mem(tag_id, _) => unreachable!("tag {tag_id} is not stored in memory"),
+            RawCompound::PackedList(tag_id, list) => {
+                self.numbers("", numbers::<E>(list, *tag_id), *tag_id)?
+            }
+            RawCompound::List(_, list) => {
+                self.out.push('[');
+                self.depth += 1;
+                for (index, element) in list.iter().enumerate() {
+                    self.separator(index == 0);
+                    self.path.push(PathSegment::Index(index));
+                    self.value(element)?;
+                    self.path.pop();
+                }
+                self.close(']', list.is_empty());
+            }
+            RawCompound::Map(_, map) => {
+                let mut entries: Vec<_> = map.iter().collect();
+                entries.sort_unstable_by_key(|(key, _)| *key);
+                self.out.push('{');
+                self.depth += 1;
+                for (index, (key, value)) in entries.iter().enumerate() {
+                    self.separator(index == 0);
+                    let key_str = self.string(key)?;
+                    write_key(&mut self.out, &key_str);
+                    self.out.push_str(if self.pretty { ": " } else { ":" });
+                    self.path.push(PathSegment::Key(key));
+                    self.value(value)?;
+                    self.path.pop();
+                }
+                self.close('}', entries.is_empty());
+            }
+        }
+        Ok(())
+    }
+
+    /// Write numbers on a single line, prefixed with the array type
+    fn numbers(
+        &mut self,
+        prefix: &str,
+        numbers: impl Iterator<Item = Number>,
+        tag_id: u8,
+    ) -> Result<(), Error> {
+        self.out.push('[');
+        self.out.push_str(prefix);
+        for (index, num) in numbers.enumerate() {
+            match (index, self.pretty) {
+                (0, true) if !prefix.is_empty() => self.out.push(' '),
+                (0, _) => {}
+                (_, true) => self.out.push_str(", "),
+                (_, false) => self.out.push(','),
+            }
+            self.number(num, tag_id, Some(index))?;
+        }
+        self.out.push(']');
+        Ok(())
+    }
+
+    /// Write a number, `index` is its position in a list or array
+    fn number(&mut self, num: Number, tag_id: u8, index: Option<usize>) -> Result<(), Error> {
+        if let Number::Float(float) = num
+            && !float.is_finite()
+        {
+            let mut path = self.path.clone();
+            path.extend(index.map(PathSegment::Index));
+            let mut message = format!("{float} can not be represented in SNBT");
+            if !path.is_empty() {
+                message.push_str(&format!(" at {}", format_path(&path)));
+            }
+            return Err(PyValueError::new_err(message).into());
+        }
+        write_number(&mut self.out, num, tag_id);
+        Ok(())
+    }
+
+    fn separator(&mut self, first: bool) {
+        if !first {
+            self.out.push(',');
+        }
+        self.newline();
+    }
+
+    fn close(&mut self, bracket: char, empty: bool) {
+        self.depth -= 1;
+        if !empty {
+            self.newline();
+        }
+        self.out.push(bracket);
+    }
+
+    fn newline(&mut self) {
+        if self.pretty {
+            self.out.push('\n');
+            self.out.extend(std::iter::repeat_n("    ", self.depth));
+        }
+    }
+
+    fn string(&self, string: &'a [u8]) -> ParseResult<Cow<'a, str>> {
+        E::decode_string(string)
+            .ok_or_else(|| ParseError::invalid_string(string, self.data, &self.path))
+    }
+}
+
+fn write_number(out: &mut String, num: Number, tag_id: u8) {
+    let _ = match (num, tag_id) {
+        (Number::Int(int), 1) => write!(out, "{int}b"),
+        (Number::Int(int), 2) => write!(out, "{int}s"),
+        (Number::Int(int), 3) => write!(out, "{int}"),
+        (Number::Int(int), 4) => write!(out, "{int}L"),
+        // Debug formatting always includes a decimal point or exponent
+        (Number::Float(float), 5) => write!(out, "{:?}f", float as f32),
+        (Number::Float(float), 6) => write!(out, "{float:?}d"),
+        _ => unreachable!("number does not match tag {tag_id}"),
+    };
+}
+
+fn write_key(out: &mut String, key: &str) {
+    let is_bare = !key.is_empty()
+        && key
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || b"._+-".contains(&byte));
+    match is_bare {
+        true => out.push_str(key),
+        false => write_quoted(out, key),
+    }
+}
+
+/// Quote like Minecraft does, preferring double quotes unless the string contains them
+fn write_quoted(out: &mut String, string: &str) {
+    let quote = match string.contains('"') && !string.contains('\'') {
+        true => '\'',
+        false => '"',
+    };
+    out.push(quote);
+    for char in string.chars() {
+        if char == quote || char == '\\' {
+            out.push('\\');
+        }
+        out.push(char);
+    }
+    out.push(quote);
+}
+
+/// Parse SNBT following the grammar of the vanilla `/data` command and encode it as NBT.
+///
+/// The root tag is written without a name if `root_name` is `None`.
+pub(crate) fn from_snbt(text: &str, root_name: Option<&str>, flavor: Flavor) -> PyResult<Vec<u8>> {
+    let mut parser = Parser {
+        text,
+        pos: 0,
+        depth: 0,
+    };
+    let tag = parser.value()?;
+    parser.skip_whitespace();
+    if parser.pos != text.len() {
+        return Err(parser.error("Trailing data"));
+    }
+    match flavor {
+        Flavor::Java => tag.encode::<BigEndian>(root_name),
+        Flavor::Bedrock => tag.encode::<LittleEndian>(root_name),
+        Flavor::BedrockNetwork => tag.encode::<NetworkLittleEndian>(root_name),
+    }
+    .map_err(PyValueError::new_err)
+}
+
+/// Owned tree produced by the parser
+enum Tag {
+    Number(Number, u8),
+    String(String),
+    List(u8, Vec<Tag>),
+    Compound(Vec<(String, Tag)>),
+    /// Tag id of the array and its elements
+    Array(u8, Vec<i64>),
+}
+
+impl Tag {
+    fn tag_id(&self) -> u8 {
+        match self {
+            Tag::Number(_, tag_id) | Tag::Array(tag_id, _) => *tag_id,
+            Tag::String(_) => 8,
+            Tag::List(..) => 9,
+            Tag::Compound(_) => 10,
+        }
+    }
+
+    fn encode<E: Encoding>(&self, root_name: Option<&str>) -> Result<Vec<u8>, String> {
+        let mut writer = Writer::<E>::new();
+        writer.tag_id(self.tag_id());
+        if let Some(root_name) = root_name {
+            writer.string(root_name)?;
+        }
+        self.write(&mut writer)?;
+        Ok(writer.out)
+    }
+
+    fn write<E: Encoding>(&self, writer: &mut Writer<E>) -> Result<(), String> {
+        match self {
+            Tag::Number(num, tag_id) => writer.number(*num, *tag_id),
+            Tag::String(string) => writer.string(string)?,
+            Tag::List(element_id, list) => {
+                writer.tag_id(*element_id);
+                writer.len(list.len())?;
+                for element in list {
+                    element.write(writer)?;
+                }
+            }
+            Tag::Compound(entries) => {
+                for (key, value) in entries {
+                    writer.tag_id(value.tag_id());
+                    writer.string(key)?;
+                    value.write(writer)?;
+                }
+                writer.tag_id(0);
+            }
+            Tag::Array(tag_id, array) => {
+                writer.len(array.len())?;
+                for int in array {
+                    writer.number(Number::Int(*int), array_element_id(*tag_id));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn array_element_id(array_id: u8) -> u8 {
+    match array_id {
+        7 => 1,
+        11 => 3,
+        _ => 4,
+    }
+}
+
+const TAG_NAMES: [&str; 13] = [
+    "TAG_End",
+    "TAG_Byte",
+    "TAG_Short",
+    "TAG_Int",
+    "TAG_Long",
+    "TAG_Float",
+    "TAG_Double",
+    "TAG_Byte_Array",
+    "TAG_String",
+    "TAG_List",
+    "TAG_Compound",
+    "TAG_Int_Array",
+    "TAG_Long_Array",
+];
+
+/// How deeply compounds and lists may be nested, like in game
+pub(crate) const MAX_DEPTH: usize = 512;
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+    /// Number of compounds and lists the parser is currently in
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn value(&mut self) -> PyResult<Tag> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{' | '[') => self.nested(),
+            Some('"' | '\'') => Ok(Tag::String(self.quoted()?)),
+            _ => {
+                let word = self.unquoted();
+                match word.is_empty() {
+                    true => Err(self.error("Expected value")),
+                    false => Ok(infer_type(word)),
+                }
+            }
+        }
+    }
+
+    fn nested(&mut self) -> PyResult<Tag> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error(format!("Nested deeper than {MAX_DEPTH} levels")));
+        }
+        self.depth += 1;
+        let tag = match self.peek() {
+            Some('{') => self.compound(),
+            _ => self.list(),
+        };
+        self.depth -= 1;
+        tag
+    }
+
+    fn compound(&mut self) -> PyResult<Tag> {
+        self.expect('{')?;
+        let mut entries: Vec<(String, Tag)> = Vec::new();
+        self.skip_whitespace();
+        if self.eat('}') {
+            return Ok(Tag::Compound(entries));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = match self.peek() {
+                Some('"' | '\'') => self.quoted()?,
+                _ => match self.unquoted() {
+                    "" => return Err(self.error("Expected key")),
+                    key => key.to_owned(),
+                },
+            };
+            self.skip_whitespace();
+            self.expect(':')?;
+            let value = self.value()?;
+            // later entries replace earlier ones, like they do in game
+            match entries.iter_mut().find(|(existing, _)| *existing == key) {
+                Some(entry) => entry.1 = value,
+                None => entries.push((key, value)),
+            }
+            if !self.separator('}')? {
+                return Ok(Tag::Compound(entries));
+            }
+        }
+    }
+
+    fn list(&mut self) -> PyResult<Tag> {
+        self.expect('[')?;
+        let array_id = match &self.text.as_bytes()[self.pos..] {
+            [b'B', b';', ..] => Some(7),
+            [b'I', b';', ..] => Some(11),
+            [b'L', b';', ..] => Some(12),
+            _ => None,
+        };
+        if let Some(array_id) = array_id {
+            self.pos += 2;
+            return self.array(array_id);
+        }
+        let mut list = Vec::new();
+        self.skip_whitespace();
+        if self.eat(']') {
+            return Ok(Tag::List(0, list));
+        }
+        loop {
+            let start = self.pos;
+            let element = self.value()?;
+            if let Some(first) = list.first().map(Tag::tag_id)
+                && element.tag_id() != first
+            {
+                self.pos = start;
+                return Err(self.error(format!(
+                    "Can't insert {} into list of {}",
+                    TAG_NAMES[element.tag_id() as usize],
+                    TAG_NAMES[first as usize]
+                )));
+            }
+            list.push(element);
+            if !self.separator(']')? {
+                let element_id = list[0].tag_id();
+                return Ok(Tag::List(element_id, list));
+            }
+        }
+    }
+
+    fn array(&mut self, array_id: u8) -> PyResult<Tag> {
+        let element_id = array_element_id(array_id);
+        let mut array = Vec::new();
+        self.skip_whitespace();
+        if self.eat(']') {
+            return Ok(Tag::Array(array_id, array));
+        }
+        loop {
+            let start = self.pos;
+            match self.value()? {
+                Tag::Number(Number::Int(int), tag_id) if tag_id == element_id => array.push(int),
+                element => {
+                    self.pos = start;
+                    return Err(self.error(format!(
+                        "Can't insert {} into {}",
+                        TAG_NAMES[element.tag_id() as usize],
+                        TAG_NAMES[array_id as usize]
+                    )));
+                }
+            }
+            if !self.separator(']')? {
+                return Ok(Tag::Array(array_id, array));
+            }
+        }
+    }
+
+    /// Consume a `,` or the closing bracket, returning whether another element follows
+    fn separator(&mut self, close: char) -> PyResult<bool> {
+        self.skip_whitespace();
+        if self.eat(',') {
+            Ok(true)
+        } else if self.eat(close) {
+            Ok(false)
+        } else {
+            Err(self.error(format!("Expected ',' or '{close}'")))
+        }
+    }
+
+    fn quoted(&mut self) -> PyResult<String> {
+        let quote = self.next().unwrap();
+        let mut string = String::new();
+        loop {
+            match self.next() {
+                Some(char) if char == quote => return Ok(string),
+                Some('\\') => {
+                    let escape_start = self.pos - 1;
+                    let escaped = match self.next() {
+                        Some(char @ ('\\' | '"' | '\'')) => Some(char),
+                        Some('n') => Some('\n'),
+                        Some('t') => Some('\t'),
+                        Some('r') => Some('\r'),
+                        Some('b') => Some('\x08'),
+                        Some('f') => Some('\x0c'),
+                        Some('s') => Some(' '),
+                        Some('x') => self.hex_escape(2),
+                        Some('u') => self.hex_escape(4),
+                        Some('U') => self.hex_escape(8),
+                        _ => None,
+                    };
+                    match escaped {
+                        Some(char) => string.push(char),
+                        None => {
+                            self.pos = escape_start;
+                            return Err(self.error("Invalid escape sequence"));
+                        }
+                    }
+                }
+                Some(char) => string.push(char),
+                None => return Err(self.error("Unterminated string")),
+            }
+        }
+    }
+
+    fn hex_escape(&mut self, digits: usize) -> Option<char> {
+        let hex = self.text.get(self.pos..self.pos + digits)?;
+        let char = u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)?;
+        self.pos += digits;
+        Some(char)
+    }
+
+    fn unquoted(&mut self) -> &str {
+        let start = self.pos;
+        while let Some(char) = self.peek()
+            && (char.is_ascii_alphanumeric() || matches!(char, '_' | '-' | '.' | '+'))
+        {
+            self.pos += 1;
+        }
+        &self.text[start..self.pos]
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.next();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> PyResult<()> {
+        match self.eat(expected) {
+            true => Ok(()),
+            false => Err(self.error(format!("Expected '{expected}'"))),
+        }
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        let matches = self.peek() == Some(expected);
+        if matches {
+            self.pos += expected.len_utf8();
+        }
+        matches
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let char = self.peek()?;
+        self.pos += char.len_utf8();
+        Some(char)
+    }
+
+    fn error(&self, message: impl Into<String>) -> PyErr {
+        PyValueError::new_err(format!(
+            "{} at position {}",
+            message.into(),
+            self.text[..self.pos].chars().count()
+        ))
+    }
+}
+
+/// Type an unquoted word the way the game does, falling back to a string
+fn infer_type(word: &str) -> Tag {
+    if word.eq_ignore_ascii_case("true") {
+        return Tag::Number(Number::Int(1), 1);
+    }
+    if word.eq_ignore_ascii_case("false") {
+        return Tag::Number(Number::Int(0), 1);
+    }
+    let (body, suffix) = match word.as_bytes().last().map(u8::to_ascii_lowercase) {
+        Some(suffix @ (b'b' | b's' | b'l' | b'f' | b'd')) => {
+            (&word[..word.len() - 1], Some(suffix))
+        }
+        _ => (word, None),
+    };
+    let number = match suffix {
+        Some(b'b') if is_integer(body) => body.parse::<i8>().ok().map(|int| (int.into(), 1)),
+        Some(b's') if is_integer(body) => body.parse::<i16>().ok().map(|int| (int.into(), 2)),
+        Some(b'l') if is_integer(body) => body.parse::<i64>().ok().map(|int| (int, 4)),
+        None if is_integer(body) => body.parse::<i32>().ok().map(|int| (int.into(), 3)),
+        _ => None,
+    }
+    .map(|(int, tag_id)| Tag::Number(Number::Int(int), tag_id))
+    .or_else(|| match suffix {
+        Some(b'f') if is_decimal(body, false) => body
+            .parse::<f32>()
+            .ok()
+            .map(|float| Tag::Number(Number::Float(float.into()), 5)),
+        Some(b'd') if is_decimal(body, false) => body
+            .parse::<f64>()
+            .ok()
+            .map(|float| Tag::Number(Number::Float(float), 6)),
+        None if is_decimal(word, true) => word
+            .parse::<f64>()
+            .ok()
+            .map(|float| Tag::Number(Number::Float(float), 6)),
+        _ => None,
+    });
+    number.unwrap_or_else(|| Tag::String(word.to_owned()))
+}
+
+/// `[-+]?(?:0|[1-9][0-9]*)`
+fn is_integer(word: &str) -> bool {
+    let digits = word.strip_prefix(['-', '+']).unwrap_or(word);
+    match digits.as_bytes() {
+        [b'0'] => true,
+        [b'1'..=b'9', rest @ ..] => rest.iter().all(u8::is_ascii_digit),
+        _ => false,
+    }
+}
+
+/// `[-+]?(?:[0-9]+[.]?|[0-9]*[.][0-9]+)(?:e[-+]?[0-9]+)?`, where the `.` is not optional with `require_dot`
+fn is_decimal(word: &str, require_dot: bool) -> bool {
+    let word = word.strip_prefix(['-', '+']).unwrap_or(word);
+    let (mantissa, exponent) = match word.find(['e', 'E']) {
+        Some(index) => (&word[..index], Some(&word[index + 1..])),
+        None => (word, None),
+    };
+    let (integer, fraction) = match mantissa.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (mantissa, None),
+    };
+    let all_digits = |part: &str| part.bytes().all(|byte| byte.is_ascii_digit());
+    let mantissa_valid = match fraction {
+        Some(fraction) => {
+            all_digits(integer)
+                && all_digits(fraction)
+                && !(integer.is_empty() && fraction.is_empty())
+        }
+        None => !require_dot && !integer.is_empty() && all_digits(integer),
+    };
+    let exponent_valid = exponent.is_none_or(|exponent| {
+        let digits = exponent.strip_prefix(['-', '+']).unwrap_or(exponent);
+        !digits.is_empty() && all_digits(digits)
+    });
+    mantissa_valid && exponent_valid
+}
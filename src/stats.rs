@@ -0,0 +1,42 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// How a comparison was decided
+#[derive(Clone, Copy)]
+pub(crate) enum Path {
+    /// Both buffers were byte-identical
+    Identical,
+    /// Walking both buffers in lockstep decided the result
+    Streamed,
+    /// A buffer was malformed or chunk sections had to be decoded, so both were parsed fully
+    Parsed,
+    /// Key order diverged and a compound was compared as maps while streaming
+    MapFallback,
+}
+
+const PATHS: [(Path, &str); 4] = [
+    (Path::Identical, "identical"),
+    (Path::Streamed, "streamed"),
+    (Path::Parsed, "parsed"),
+    (Path::MapFallback, "map_fallback"),
+];
+
+static COUNTERS: [AtomicU64; 4] = [const { AtomicU64::new(0) }; 4];
+
+pub(crate) fn record(path: Path) {
+    COUNTERS[path as usize].fetch_add(1, Ordering::Relaxed);
+}
+
+/// The current counters by name, optionally resetting them
+pub(crate) fn snapshot(reset: bool) -> Vec<(&'static str, u64)> {
+    PATHS
+        .iter()
+        .map(|&(path, name)| {
+            let counter = &COUNTERS[path as usize];
+            let value = match reset {
+                true => counter.swap(0, Ordering::Relaxed),
+                false => counter.load(Ordering::Relaxed),
+            };
+            (name, value)
+        })
+        .collect()
+}
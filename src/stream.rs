@@ -0,0 +1,321 @@
+use crate::chunk;
+use crate::equality::Equality;
+use crate::error::{ParseError, ParseResult};
+use crate::ignore::{Matcher, prune};
+use crate::path::PathSegment;
+use crate::stats;
+use crate::{
+    BigEndian, Encoding, Flavor, LittleEndian, NetworkLittleEndian, Number, Options, ParseFuncType,
+    ParseOptions, RawCompound, TAG_SIZE_LUT, get_u8, read_root_header, split_off,
+    strip_bedrock_header,
+};
+use std::marker::PhantomData;
+
+/// Compare two decompressed buffers by walking them in lockstep, stopping at the first difference.
+/// Compounds are only parsed into maps once their key order diverges.
+/// Also returns the `DataVersion` of both roots, which is read even if it is ignored.
+///
+/// Returns `None` if a buffer is malformed, so the caller can parse it again for a proper error.
+pub(crate) fn stream_compare(
+    left: &[u8],
+    right: &[u8],
+    options: &Options,
+) -> Option<(bool, [Option<i32>; 2])> {
+    match options.parse.flavor {
+        Flavor::Java => Walker::<BigEndian>::new(options).compare_root(left, right),
+        Flavor::Bedrock => Walker::<LittleEndian>::new(options)
+            .compare_root(strip_bedrock_header(left), strip_bedrock_header(right)),
+        Flavor::BedrockNetwork => {
+            Walker::<NetworkLittleEndian>::new(options).compare_root(left, right)
+        }
+    }
+    .ok()
+}
+
+/// Read the `DataVersion` of the root by walking a decompressed buffer without building a tree
+pub(crate) fn data_version(data: &[u8], options: &ParseOptions) -> ParseResult<Option<i32>> {
+    match options.flavor {
+        Flavor::Java => skip_root::<BigEndian>(data, options),
+        Flavor::Bedrock => skip_root::<LittleEndian>(strip_bedrock_header(data), options),
+        Flavor::BedrockNetwork => skip_root::<NetworkLittleEndian>(data, options),
+    }
+}
+
+struct Walker<'o, E> {
+    options: &'o Options,
+    equality: Equality<'o>,
+    encoding: PhantomData<E>,
+}
+
+impl<'o, E: Encoding> Walker<'o, E> {
+    fn new(options: &'o Options) -> Self {
+        Walker {
+            options,
+            equality: options.equality(),
+            encoding: PhantomData,
+        }
+    }
+
+    fn compare_root(&self, left: &[u8], right: &[u8]) -> ParseResult<(bool, [Option<i32>; 2])> {
+        let mut data_versions = [None, None];
+        let equal = self.walk_root(left, right, &mut data_versions)?;
+        // the walk stops at the first difference, but a full parse would reject malformed data after it
+        if !equal {
+            data_versions = [
+                skip_root::<E>(left, &self.options.parse)?,
+                skip_root::<E>(right, &self.options.parse)?,
+            ];
+        }
+        Ok((equal, data_versions))
+    }
+
+    fn walk_root(
+        &self,
+        mut left: &[u8],
+        mut right: &[u8],
+        data_versions: &mut [Option<i32>; 2],
+    ) -> ParseResult<bool> {
+        let (left_id, _) = read_root_header::<E>(&mut left, &self.options.parse)?;
+        let (right_id, _) = read_root_header::<E>(&mut right, &self.options.parse)?;
+        if left_id != right_id {
+            return Ok(false);
+        }
+        let matcher = Matcher::new(&self.options.ignore);
+        let rules = self.options.rules.matcher();
+        match left_id {
+            10 if rules.matched().is_none() => {
+                self.compounds_eq(&mut left, &mut right, &matcher, &rules, Some(data_versions))
+            }
+            _ => self.values_eq(&mut left, &mut right, left_id, &matcher, &rules),
+        }
+    }
+
+    fn values_eq(
+        &self,
+        left: &mut &[u8],
+        right: &mut &[u8],
+        tag_id: u8,
+        matcher: &Matcher,
+        rules: &Matcher,
+    ) -> ParseResult<bool> {
+        match tag_id {
+            // tags with a rule are only compared after parsing
+            _ if rules.matched().is_some() => {
+                self.trees_eq(left, right, tag_id, matcher, rules, None)
+            }
+            9 => self.lists_eq(left, right, matcher, rules),
+            10 => self.compounds_eq(left, right, matcher, rules, None),
+            _ => {
+                let parse_func = parse_func::<E>(tag_id, left)?;
+                match (parse_func(left)?, parse_func(right)?) {
+                    (RawCompound::Mem(_, left), RawCompound::Mem(_, right)) => {
+                        Ok(self.equality.floats.payloads_eq(tag_id, left, right))
+                    }
+                    (left, right) => Ok(left == right),
+                }
+            }
+        }
+    }
+
+    fn lists_eq(
+        &self,
+        left: &mut &[u8],
+        right: &mut &[u8],
+        matcher: &Matcher,
+        rules: &Matcher,
+    ) -> ParseResult<bool> {
+        let (left_start, right_start) = (*left, *right);
+        let (left_id, left_len) = (get_u8(left)?, E::get_len(left)?);
+        let (right_id, right_len) = (get_u8(right)?, E::get_len(right)?);
+        if left_len == 0 && right_len == 0 {
+            return Ok(true);
+        }
+        if (left_id == 0 && left_len != 0) || (right_id == 0 && right_len != 0) {
+            return Err(ParseError::new(
+                "Missing element type of non-empty list",
+                left,
+            ));
+        }
+        if left_id != right_id || left_len != right_len {
+            if matcher.is_empty() {
+                return Ok(false);
+            }
+            // ignored elements may still make them equal
+            (*left, *right) = (left_start, right_start);
+            return self.trees_eq(left, right, 9, matcher, rules, None);
+        }
+        if matcher.is_empty()
+            && left_id < 7
+            && E::is_fixed_width(left_id)
+            && self.equality.floats.is_bytewise(left_id)
+        {
+            let byte_len = usize::from(TAG_SIZE_LUT[left_id as usize])
+                .checked_mul(left_len as usize)
+                .ok_or_else(|| ParseError::new("Overflow when calculating list length", left))?;
+            return Ok(split_off(left, byte_len)? == split_off(right, byte_len)?);
+        }
+        let parse_func = parse_func::<E>(left_id, left)?;
+        for index in 0..left_len as usize {
+            match matcher.child(PathSegment::Index(index)) {
+                Some(child) => {
+                    let rules = rules.descend(PathSegment::Index(index));
+                    if !self.values_eq(left, right, left_id, &child, &rules)? {
+                        return Ok(false);
+                    }
+                }
+                None => {
+                    parse_func(left)?;
+                    parse_func(right)?;
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Compare two compounds, recording their `DataVersion` in `data_versions` if they are the roots
+    fn compounds_eq(
+        &self,
+        left: &mut &[u8],
+        right: &mut &[u8],
+        matcher: &Matcher,
+        rules: &Matcher,
+        mut data_versions: Option<&mut [Option<i32>; 2]>,
+    ) -> ParseResult<bool> {
+        loop {
+            let (left_start, right_start) = (*left, *right);
+            match (
+                next_entry::<E>(
+                    left,
+                    matcher,
+                    data_versions.as_deref_mut().map(|v| &mut v[0]),
+                )?,
+                next_entry::<E>(
+                    right,
+                    matcher,
+                    data_versions.as_deref_mut().map(|v| &mut v[1]),
+                )?,
+            ) {
+                (None, None) => return Ok(true),
+                (Some((left_id, left_name, child)), Some((right_id, right_name, _)))
+                    if left_name == right_name =>
+                {
+                    let rules = rules.descend(PathSegment::Key(left_name));
+                    if left_id != right_id
+                        || !self.values_eq(left, right, left_id, &child, &rules)?
+                    {
+                        return Ok(false);
+                    }
+                }
+                (Some(_), Some(_)) => {
+                    // key order diverges, match the remaining entries by name
+                    stats::record(stats::Path::MapFallback);
+                    (*left, *right) = (left_start, right_start);
+                    return self.trees_eq(left, right, 10, matcher, rules, data_versions);
+                }
+                _ => return Ok(false),
+            }
+        }
+    }
+
+    /// Parse the rest of both values and compare the resulting trees
+    fn trees_eq(
+        &self,
+        left: &mut &[u8],
+        right: &mut &[u8],
+        tag_id: u8,
+        matcher: &Matcher,
+        rules: &Matcher,
+        data_versions: Option<&mut [Option<i32>; 2]>,
+    ) -> ParseResult<bool> {
+        let parse_func = parse_func::<E>(tag_id, left)?;
+        let (mut left, mut right) = (parse_func(left)?, parse_func(right)?);
+        for (version, tree) in data_versions.into_iter().flatten().zip([&left, &right]) {
+            *version = chunk::data_version(tree).or(*version);
+        }
+        prune(&mut left, matcher);
+        prune(&mut right, matcher);
+        Ok(self.equality.trees_eq(&left, &right, rules))
+    }
+}
+
+/// Read the header of the next entry that is not ignored, `None` at the end of the compound.
+/// A `DataVersion` entry passed on the way is stored in `data_version`, even if it is ignored.
+fn next_entry<'a, 'p, E: Encoding>(
+    data: &mut &'a [u8],
+    matcher: &Matcher<'p>,
+    mut data_version: Option<&mut Option<i32>>,
+) -> ParseResult<Option<(u8, &'a [u8], Matcher<'p>)>> {
+    loop {
+        let tag_id = get_u8(data)?;
+        if tag_id == 0 {
+            return Ok(None);
+        }
+        let parse_func = parse_func::<E>(tag_id, data)?;
+        let name_len = E::get_str_len(data)?;
+        let name = split_off(data, name_len)?;
+        if let Some(data_version) = data_version.as_deref_mut()
+            && tag_id == 3
+            && name == chunk::DATA_VERSION
+            && let Number::Int(version) = E::decode_number(E::split_off_number(&mut &**data, 3)?, 3)
+        {
+            *data_version = Some(version as i32);
+        }
+        match matcher.child(PathSegment::Key(name)) {
+            Some(child) => return Ok(Some((tag_id, name, child))),
+            None => {
+                parse_func(data)?;
+            }
+        }
+    }
+}
+
+/// Check that a whole buffer is well-formed without building maps for its compounds,
+/// returning the `DataVersion` of the root
+fn skip_root<E: Encoding>(mut data: &[u8], options: &ParseOptions) -> ParseResult<Option<i32>> {
+    let (tag_id, _) = read_root_header::<E>(&mut data, options)?;
+    let mut data_version = None;
+    if tag_id != 10 {
+        skip_value::<E>(&mut data, tag_id)?;
+        return Ok(data_version);
+    }
+    let matcher = Matcher::new(&[]);
+    while let Some((tag_id, _, _)) = next_entry::<E>(&mut data, &matcher, Some(&mut data_version))?
+    {
+        skip_value::<E>(&mut data, tag_id)?;
+    }
+    Ok(data_version)
+}
+
+fn skip_value<E: Encoding>(data: &mut &[u8], tag_id: u8) -> ParseResult<()> {
+    match tag_id {
+        9 => {
+            let start = *data;
+            let (element_id, len) = (get_u8(data)?, E::get_len(data)?);
+            if len == 0 || !matches!(element_id, 9 | 10) {
+                // lists of other tags are parsed without nested trees
+                *data = start;
+                return parse_func::<E>(9, data)?(data).map(drop);
+            }
+            for _ in 0..len {
+                skip_value::<E>(data, element_id)?;
+            }
+            Ok(())
+        }
+        10 => {
+            let matcher = Matcher::new(&[]);
+            while let Some((tag_id, _, _)) = next_entry::<E>(data, &matcher, None)? {
+                skip_value::<E>(data, tag_id)?;
+            }
+            Ok(())
+        }
+        _ => parse_func::<E>(tag_id, data)?(data).map(drop),
+    }
+}
+
+fn parse_func<E: Encoding>(tag_id: u8, data: &[u8]) -> ParseResult<ParseFuncType> {
+    E::TAG_LUT
+        .get(tag_id as usize)
+        .copied()
+        .flatten()
+        .ok_or_else(|| ParseError::new(format!("Unknown tag id: {tag_id}"), data))
+}
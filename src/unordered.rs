@@ -0,0 +1,195 @@
+use crate::fingerprint::fingerprint;
+use crate::ignore::PathPattern;
+use crate::rules::{Rule, Rules};
+use crate::{RawCompound, TAG_SIZE_LUT};
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyMapping, PyString};
+use std::collections::HashMap;
+
+/// Collect the `unordered` argument of the python API,
+/// either a collection of patterns or a mapping from patterns to keys
+pub(crate) fn extract_unordered(
+    unordered: Option<&Bound<'_, PyAny>>,
+    rules: &mut Rules,
+) -> PyResult<()> {
+    let Some(unordered) = unordered else {
+        return Ok(());
+    };
+    if unordered.is_instance_of::<PyString>() {
+        return Err(PyTypeError::new_err(
+            "unordered must be a collection of patterns, not a single str",
+        ));
+    }
+    let entries: Vec<(String, Option<String>)> = match unordered.cast::<PyMapping>() {
+        Ok(mapping) => mapping.items()?.extract()?,
+        Err(_) => unordered
+            .try_iter()?
+            .map(|pattern| Ok((pattern?.extract()?, None)))
+            .collect::<PyResult<_>>()?,
+    };
+    for (pattern, key) in entries {
+        let pattern = PathPattern::parse(&pattern).map_err(|e| {
+            PyValueError::new_err(format!("Invalid unordered pattern {pattern:?}: {e}"))
+        })?;
+        rules.push(pattern, Rule::Unordered(key.map(String::into_bytes)));
+    }
+    Ok(())
+}
+
+/// The elements of a list, with packed lists split into separate tags
+pub(crate) fn elements<'b, 'a>(
+    list: &'b RawCompound<'a>,
+    unpacked: &'b mut Vec<RawCompound<'a>>,
+) -> Option<&'b [RawCompound<'a>]> {
+    match list {
+        RawCompound::List(_, list) => Some(list),
+        RawCompound::PackedList(tag_id, payload) => {
+            let tag_size = TAG_SIZE_LUT[*tag_id as usize] as usize;
+            *unpacked = payload
+                .chunks_exact(tag_size)
+                .map(|element| RawCompound::Mem(*tag_id, element))
+                .collect();
+            Some(unpacked)
+        }
+        _ => None,
+    }
+}
+
+/// How the elements of two unordered lists correspond, by index
+pub(crate) struct Pairing {
+    pub(crate) equal: Vec<(usize, usize)>,
+    /// Elements with the same key that are not equal
+    pub(crate) changed: Vec<(usize, usize)>,
+    pub(crate) removed: Vec<usize>,
+    pub(crate) added: Vec<usize>,
+}
+
+/// Pair every element with an equal one on the other side.
+/// Elements with a `key` are only paired with elements with the same key, and paired up even if they differ.
+///
+/// Pairing greedily is only optimal if `eq` is transitive, otherwise augmenting paths are searched
+/// to find as many equal pairs as possible.
+pub(crate) fn pair_up(
+    left: &[RawCompound],
+    right: &[RawCompound],
+    key: Option<&[u8]>,
+    transitive: bool,
+    eq: impl Fn(usize, usize) -> bool,
+) -> Pairing {
+    let key_of = |tree: &RawCompound| match (key, tree) {
+        (Some(key), RawCompound::Map(_, map)) => map
+            .get(key)
+            .map(|key| fingerprint(key, &Rules::default(), None)),
+        _ => None,
+    };
+    let mut candidates: HashMap<_, Vec<usize>> = HashMap::new();
+    for (index, element) in right.iter().enumerate() {
+        candidates.entry(key_of(element)).or_default().push(index);
+    }
+    let left_keys: Vec<_> = left.iter().map(key_of).collect();
+    let neighbours = |index: usize| {
+        candidates
+            .get(&left_keys[index])
+            .map_or(&[][..], Vec::as_slice)
+    };
+    let mut matching = Matching {
+        left: vec![None; left.len()],
+        right: vec![None; right.len()],
+    };
+    for index in 0..left.len() {
+        let found = neighbours(index)
+            .iter()
+            .find(|&&other| matching.right[other].is_none() && eq(index, other));
+        if let Some(&other) = found {
+            matching.pair(index, other);
+        }
+    }
+    if !transitive {
+        for index in 0..left.len() {
+            if matching.left[index].is_none() {
+                matching.augment(index, neighbours, &eq);
+            }
+        }
+    }
+    let mut pairing = Pairing {
+        equal: (matching.left.iter().enumerate())
+            .filter_map(|(index, other)| Some((index, (*other)?)))
+            .collect(),
+        changed: Vec::new(),
+        removed: Vec::new(),
+        added: Vec::new(),
+    };
+    for (index, element_key) in left_keys.iter().enumerate() {
+        if matching.left[index].is_some() {
+            continue;
+        }
+        let same_key = element_key
+            .is_some()
+            .then(|| {
+                neighbours(index)
+                    .iter()
+                    .find(|&&other| matching.right[other].is_none())
+            })
+            .flatten();
+        match same_key {
+            Some(&other) => {
+                matching.pair(index, other);
+                pairing.changed.push((index, other));
+            }
+            None => pairing.removed.push(index),
+        }
+    }
+    pairing.added = (0..right.len())
+        .filter(|&index| matching.right[index].is_none())
+        .collect();
+    pairing
+}
+
+/// The element each element is paired with, by index
+struct Matching {
+    left: Vec<Option<usize>>,
+    right: Vec<Option<usize>>,
+}
+
+impl Matching {
+    fn pair(&mut self, left: usize, right: usize) {
+        self.left[left] = Some(right);
+        self.right[right] = Some(left);
+    }
+
+    /// Search a path from the unpaired `start` that alternates between unpaired and paired edges
+    /// and ends at an unpaired right element, then flip it to gain one pair
+    fn augment<'n>(
+        &mut self,
+        start: usize,
+        neighbours: impl Fn(usize) -> &'n [usize],
+        eq: impl Fn(usize, usize) -> bool,
+    ) {
+        let mut visited = vec![false; self.right.len()];
+        // left elements on the path, the position of their next candidate and the candidate taken
+        let mut path = vec![(start, 0, 0)];
+        while let Some(&(index, position, _)) = path.last() {
+            let Some(&other) = neighbours(index).get(position) else {
+                path.pop();
+                continue;
+            };
+            let last = path.len() - 1;
+            path[last].1 += 1;
+            if visited[other] || !eq(index, other) {
+                continue;
+            }
+            visited[other] = true;
+            path[last].2 = other;
+            match self.right[other] {
+                Some(next) => path.push((next, 0, 0)),
+                None => {
+                    for (index, _, other) in path {
+                        self.pair(index, other);
+                    }
+                    return;
+                }
+            }
+        }
+    }
+}
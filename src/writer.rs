@@ -0,0 +1,51 @@
+use crate::{Encoding, Number};
+use std::marker::PhantomData;
+
+/// Appends tags in the encoding `E` to a buffer
+pub(crate) struct Writer<E> {
+    pub(crate) out: Vec<u8>,
+    encoding: PhantomData<E>,
+}
+
+impl<E: Encoding> Writer<E> {
+    pub(crate) fn new() -> Self {
+        Writer {
+            out: Vec::new(),
+            encoding: PhantomData,
+        }
+    }
+
+    pub(crate) fn tag_id(&mut self, tag_id: u8) {
+        self.out.push(tag_id);
+    }
+
+    pub(crate) fn number(&mut self, num: Number, tag_id: u8) {
+        E::put_number(&mut self.out, num, tag_id);
+    }
+
+    pub(crate) fn string(&mut self, string: &str) -> Result<(), String> {
+        let string = E::encode_string(string);
+        if string.len() > E::MAX_STR_LEN {
+            return Err(format!(
+                "String is too long ({} bytes encoded)",
+                string.len()
+            ));
+        }
+        E::put_str_len(&mut self.out, string.len());
+        self.out.extend_from_slice(&string);
+        Ok(())
+    }
+
+    /// Write the length of a list or array
+    pub(crate) fn len(&mut self, len: usize) -> Result<(), String> {
+        let len = u32::try_from(len).map_err(|_| format!("Too many elements ({len})"))?;
+        E::put_len(&mut self.out, len);
+        Ok(())
+    }
+
+    pub(crate) fn byte_array(&mut self, bytes: &[u8]) -> Result<(), String> {
+        self.len(bytes.len())?;
+        self.out.extend_from_slice(bytes);
+        Ok(())
+    }
+}
@@ -0,0 +1,159 @@
+import array
+import random
+
+import pytest
+
+from nbtcompare import compare, diff, diff_blocks, dumps, fingerprint
+from nbtcompare.tags import Byte, Int
+
+NAMES = [f"minecraft:block_{i}" for i in range(20)]
+
+
+def pack(indices, bits, spanning):
+    """Pack palette indices into longs, across long boundaries if ``spanning``."""
+    longs = []
+    if spanning:
+        total = sum(index << (i * bits) for i, index in enumerate(indices))
+        longs = [total >> (64 * i) & (1 << 64) - 1 for i in range((len(indices) * bits + 63) // 64)]
+    else:
+        per_long = 64 // bits
+        for start in range(0, len(indices), per_long):
+            chunk = indices[start : start + per_long]
+            longs.append(sum(index << (i * bits) for i, index in enumerate(chunk)))
+    return array.array("q", [value - (1 << 64) if value >= 1 << 63 else value for value in longs])
+
+
+def palette_of(entries, reorder):
+    palette = list(dict.fromkeys(entries))
+    return palette[::-1] if reorder else palette
+
+
+def container(entries, minimum_bits, reorder=False):
+    palette = palette_of(entries, reorder)
+    result = {"palette": palette}
+    if len(palette) > 1:
+        bits = max(minimum_bits, (len(palette) - 1).bit_length())
+        result["data"] = pack([palette.index(entry) for entry in entries], bits, spanning=False)
+    return result
+
+
+def modern_chunk(blocks, biomes, reorder=False):
+    block_states = container(blocks, 4, reorder)
+    block_states["palette"] = [{"Name": name} for name in block_states["palette"]]
+    section = {"Y": Byte(-4), "block_states": block_states, "biomes": container(biomes, 1, reorder)}
+    return dumps({"DataVersion": Int(3465), "sections": [section], "Status": "minecraft:full"})
+
+
+def flat_chunk(data_version, blocks, spanning, reorder=False):
+    """A 1.13 to 1.17 chunk with its sections inside the ``Level`` wrapper."""
+    palette = palette_of(blocks, reorder)
+    bits = max(4, (len(palette) - 1).bit_length())
+    section = {
+        "Y": Byte(0),
+        "Palette": [{"Name": name} for name in palette],
+        "BlockStates": pack([palette.index(block) for block in blocks], bits, spanning),
+    }
+    return dumps({"DataVersion": Int(data_version), "Level": {"Sections": [section], "xPos": 0, "zPos": 0}})
+
+
+def nibbles(values):
+    return bytes(values[i] & 15 | (values[i + 1] & 15) << 4 for i in range(0, len(values), 2))
+
+
+def numeric_chunk(ids, data, add=None):
+    """A pre-1.13 chunk with numeric block ids and their data values in nibble arrays."""
+    section = {"Y": Byte(0), "Blocks": bytes(block & 255 for block in ids), "Data": nibbles(data)}
+    if add is not None:
+        section["Add"] = nibbles(add)
+    return dumps({"DataVersion": Int(1343), "Level": {"Sections": [section], "xPos": 0, "zPos": 0}})
+
+
+@pytest.fixture
+def blocks():
+    rng = random.Random(0)
+    return [rng.choice(NAMES) for _ in range(4096)]
+
+
+def test_reordered_block_states_are_equal(blocks):
+    biomes = ["minecraft:plains", "minecraft:forest"] * 32
+    left = modern_chunk(blocks, biomes)
+    right = modern_chunk(blocks, biomes, reorder=True)
+    assert not compare(left, right)
+    assert compare(left, right, chunk_aware=True)
+    assert diff(left, right, chunk_aware=True) == []
+    assert fingerprint(left, chunk_aware=True) == fingerprint(right, chunk_aware=True)
+    assert diff_blocks(left, right) == {}
+
+
+def test_changed_block_states(blocks):
+    biomes = ["minecraft:plains"] * 64
+    changed = list(blocks)
+    changed[0x123] = "minecraft:diamond_block"
+    left = modern_chunk(blocks, biomes)
+    right = modern_chunk(changed, biomes, reorder=True)
+    assert not compare(left, right, chunk_aware=True)
+    assert [difference.path for difference in diff(left, right, chunk_aware=True)] == [
+        "sections[0].block_states"
+    ]
+    assert diff_blocks(left, right) == {
+        -4: [(3, 1, 2, {"Name": blocks[0x123]}, {"Name": "minecraft:diamond_block"})]
+    }
+
+
+def test_changed_biomes(blocks):
+    biomes = ["minecraft:plains", "minecraft:forest"] * 32
+    changed = list(biomes)
+    changed[5] = "minecraft:desert"
+    left = modern_chunk(blocks, biomes)
+    assert compare(left, modern_chunk(blocks, biomes, reorder=True), chunk_aware=True)
+    assert not compare(left, modern_chunk(blocks, changed, reorder=True), chunk_aware=True)
+
+
+@pytest.mark.parametrize("data_version, spanning", [(1976, True), (2586, False)])
+def test_flattened_block_states(blocks, data_version, spanning):
+    changed = list(blocks)
+    changed[0x321] = "minecraft:gold_block"
+    left = flat_chunk(data_version, blocks, spanning)
+    assert not compare(left, flat_chunk(data_version, blocks, spanning, reorder=True))
+    assert compare(left, flat_chunk(data_version, blocks, spanning, reorder=True), chunk_aware=True)
+    right = flat_chunk(data_version, changed, spanning, reorder=True)
+    assert not compare(left, right, chunk_aware=True)
+    assert diff_blocks(left, right) == {0: [(1, 3, 2, {"Name": blocks[0x321]}, {"Name": "minecraft:gold_block"})]}
+
+
+@pytest.mark.parametrize("data_version, spanning", [(1976, False), (2586, True)])
+def test_packing_must_match_data_version(blocks, data_version, spanning):
+    left = flat_chunk(data_version, blocks, spanning)
+    # not decodable, so the sections are compared like any other compound
+    assert not compare(left, flat_chunk(data_version, blocks, spanning, reorder=True), chunk_aware=True)
+    with pytest.raises(ValueError):
+        diff_blocks(left, left)
+
+
+def test_numeric_blocks():
+    rng = random.Random(1)
+    ids = [rng.randrange(256) for _ in range(4096)]
+    data = [rng.randrange(16) for _ in range(4096)]
+    changed_ids, changed_data = list(ids), list(data)
+    changed_ids[5] = (ids[5] + 1) % 256
+    # odd indices are stored in the high nibble
+    changed_data[0x107] = (data[0x107] + 1) % 16
+    left = numeric_chunk(ids, data)
+    assert compare(left, numeric_chunk(ids, data), chunk_aware=True)
+    assert diff_blocks(left, numeric_chunk(changed_ids, changed_data)) == {
+        0: [
+            (5, 0, 0, (ids[5], data[5]), (changed_ids[5], data[5])),
+            (7, 1, 0, (ids[0x107], data[0x107]), (ids[0x107], changed_data[0x107])),
+        ]
+    }
+
+
+def test_numeric_blocks_add():
+    ids = [1] * 4096
+    data = [0] * 4096
+    add = [0] * 4096
+    add[9] = 1
+    left = numeric_chunk(ids, data)
+    # an Add array of zeros is the same as none at all
+    assert compare(left, numeric_chunk(ids, data, [0] * 4096), chunk_aware=True)
+    assert diff_blocks(left, numeric_chunk(ids, data, add)) == {0: [(9, 0, 0, (1, 0), (257, 0))]}
@@ -0,0 +1,29 @@
+import pytest
+
+from nbtcompare import compare, compare_many, data_version, diff, from_snbt
+
+OLD = from_snbt("{DataVersion:3465,xPos:1,zPos:2}")
+NEW = from_snbt("{zPos:2,xPos:1,DataVersion:3700}")
+
+
+def test_ignore_data_version():
+    assert not compare(OLD, NEW)
+    assert [difference.path for difference in diff(OLD, NEW)] == ["DataVersion"]
+    assert compare(OLD, NEW, ignore_data_version=True)
+    assert diff(OLD, NEW, ignore_data_version=True) == []
+
+
+def test_report_data_versions():
+    options = {"ignore_data_version": True, "report_data_versions": True}
+    assert compare(OLD, NEW, **options) == (True, (3465, 3700))
+    assert diff(OLD, NEW, **options) == ([], (3465, 3700))
+    # identical buffers are still read for their version
+    assert compare_many([(OLD, NEW), (OLD, OLD)], **options) == [(True, (3465, 3700)), (True, (3465, 3465))]
+    assert compare(OLD, from_snbt("{xPos:1}"), report_data_versions=True) == (False, (3465, None))
+
+
+@pytest.mark.parametrize("flavor", ["java", "bedrock", "bedrock_network"])
+def test_data_version(flavor):
+    assert data_version(from_snbt("{a:[1],DataVersion:3465}", flavor=flavor), flavor=flavor) == 3465
+    assert data_version(from_snbt("{a:{DataVersion:3465}}", flavor=flavor), flavor=flavor) is None
+    assert data_version(from_snbt("{DataVersion:3465}", None), nameless_root=True) == 3465
@@ -0,0 +1,65 @@
+import array
+
+import pytest
+
+from nbtcompare import dumps, from_snbt, loads
+from nbtcompare.tags import Byte, Double, Float, Int, Long, Short
+
+
+def nested(depth):
+    obj = []
+    for _ in range(depth - 1):
+        obj = [obj]
+    return obj
+
+
+def test_circular_reference():
+    obj = {}
+    obj["a"] = obj
+    with pytest.raises(ValueError, match="Circular reference"):
+        dumps(obj)
+    obj = {"a": []}
+    obj["a"].append(obj)
+    with pytest.raises(ValueError, match="Circular reference"):
+        dumps(obj)
+
+
+def test_shared_values_are_not_circular():
+    shared = {"id": 1}
+    assert dumps({"a": shared, "b": [shared, shared]}) == from_snbt("{a:{id:1},b:[{id:1},{id:1}]}")
+
+
+def test_nesting_limit():
+    assert dumps(nested(512)) == from_snbt("[" * 512 + "]" * 512)
+    with pytest.raises(ValueError, match="512"):
+        dumps(nested(513))
+    with pytest.raises(ValueError, match="512"):
+        dumps(nested(100_000))
+
+
+def test_round_trip():
+    obj = {
+        "byte": Byte(-1),
+        "short": Short(300),
+        "int": Int(-70000),
+        "long": Long(-(2**40)),
+        "float": Float(0.5),
+        "double": Double(-2.25),
+        "string": "héllo",
+        "bytes": b"\x01\xff",
+        "list": [Int(1), Int(-2)],
+        "compound": {"empty": []},
+        "ints": array.array("i", [1, -1]),
+        "longs": array.array("q", [2**40]),
+    }
+    assert loads(dumps(obj), preserve_types=True) == obj
+
+
+def test_native_types():
+    assert loads(dumps({"a": 1, "b": 2**40, "c": 0.5, "d": True, "e": [1, 2**40]}), preserve_types=True) == {
+        "a": Int(1),
+        "b": Long(2**40),
+        "c": Double(0.5),
+        "d": Byte(1),
+        "e": [Long(1), Long(2**40)],
+    }
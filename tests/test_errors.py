@@ -0,0 +1,44 @@
+import pickle
+
+import pytest
+
+from nbtcompare import NBTParseError, compare, from_snbt, loads
+
+
+def test_location():
+    data = from_snbt("{a:{b:[1,2]}}")
+    with pytest.raises(NBTParseError) as info:
+        loads(data[:-6])
+    error = info.value
+    assert isinstance(error, ValueError)
+    assert (error.offset, error.path, error.tag_id) == (16, "a.b", 9)
+    assert str(error) == "Unexpected EOF at offset 16 in a.b"
+
+
+def test_side_note():
+    with pytest.raises(NBTParseError) as info:
+        compare(from_snbt("{a:1}"), b"\x0a\x00\x00\x0e")
+    assert info.value.tag_id == 14
+    assert info.value.__notes__ == ["Occurred while parsing right"]
+
+
+def test_pickle():
+    with pytest.raises(NBTParseError) as info:
+        loads(b"\x0a\x00")
+    error = pickle.loads(pickle.dumps(info.value))
+    assert type(error) is NBTParseError
+    assert str(error) == str(info.value)
+
+
+@pytest.mark.parametrize(
+    "data",
+    [
+        # the root name is longer than the data, so the list length would be read from it
+        bytes.fromhex("0900bb0a27000002") + bytes(40),
+        # a list of 0x27000002 compounds
+        b"\x09\x00\x00\x0a\x27\x00\x00\x02" + bytes(8),
+    ],
+)
+def test_lengths_past_the_end(data):
+    with pytest.raises(NBTParseError, match="Unexpected EOF"):
+        loads(data)
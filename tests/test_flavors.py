@@ -0,0 +1,84 @@
+import array
+import struct
+
+import pytest
+
+from nbtcompare import NBTParseError, compare, diff, dumps, from_snbt, loads, to_snbt
+from nbtcompare.tags import Byte, Double, Float, Int, Long, Short
+
+VALUES = {
+    "byte": Byte(-1),
+    "short": Short(300),
+    "int": Int(-70000),
+    "long": Long(-(2**40)),
+    "float": Float(0.5),
+    "double": Double(-2.25),
+    "string": "héllo",
+    "bytes": b"\x01\xff",
+    "list": [Int(1), Int(-2)],
+    "compound": {"empty": []},
+    "ints": array.array("i", [1, -1]),
+    "longs": array.array("q", [2**40]),
+}
+
+
+def test_bedrock_round_trip():
+    data = dumps(VALUES, flavor="bedrock")
+    assert loads(data, flavor="bedrock", preserve_types=True) == VALUES
+    assert to_snbt(data, flavor="bedrock") == to_snbt(dumps(VALUES))
+
+
+def test_bedrock_little_endian():
+    assert dumps({"a": Int(1)}, flavor="bedrock") == b"\x0a\x00\x00\x03\x01\x00a\x01\x00\x00\x00\x00"
+    assert loads(b"\x0a\x00\x00\x02\x01\x00a\x2c\x01\x00", flavor="bedrock") == {"a": 300}
+
+
+def test_bedrock_level_dat_header():
+    data = dumps({"a": Int(1)}, flavor="bedrock")
+    level_dat = struct.pack("<II", 10, len(data)) + data
+    assert loads(level_dat, flavor="bedrock") == {"a": 1}
+    assert compare(level_dat, data, flavor="bedrock")
+    # only a header with the right length is skipped
+    assert loads(data, flavor="bedrock") == {"a": 1}
+
+
+def test_bedrock_compare():
+    left = from_snbt("{a:1,b:[1.0f,2.0f]}", flavor="bedrock")
+    right = from_snbt("{b:[1.0f,3.0f],a:1}", flavor="bedrock")
+    assert not compare(left, right, flavor="bedrock")
+    assert [difference.path for difference in diff(left, right, flavor="bedrock")] == ["b"]
+    assert compare(left, right, flavor="bedrock", ignore=["b"])
+
+
+def test_nameless_root_round_trip():
+    data = dumps(VALUES, None)
+    assert loads(data, nameless_root=True, preserve_types=True) == VALUES
+    assert compare(data, dumps(VALUES, None), nameless_root=True)
+    assert to_snbt(data, nameless_root=True) == to_snbt(dumps(VALUES))
+
+
+def test_nameless_root_layout():
+    assert dumps({"a": Int(1)}, None) == b"\x0a\x03\x00\x01a\x00\x00\x00\x01\x00"
+    with pytest.raises(NBTParseError):
+        loads(dumps({"a": Int(1)}, None))
+
+
+def test_nameless_root_of_any_type():
+    data = dumps(Int(5), None)
+    assert loads(data, nameless_root=True) == 5
+    with pytest.raises(NBTParseError, match="not compound"):
+        loads(data, nameless_root=True, strict_root=True)
+
+
+def test_bedrock_network_round_trip():
+    data = dumps(VALUES, flavor="bedrock_network")
+    assert loads(data, flavor="bedrock_network", preserve_types=True) == VALUES
+    assert compare(data, dumps(VALUES, flavor="bedrock_network"), flavor="bedrock_network")
+
+
+def test_bedrock_network_varints():
+    # lengths are unsigned VarInts, Int and Long values are zigzag encoded
+    assert dumps({"a": Int(1)}, flavor="bedrock_network") == bytes.fromhex("0a000301610200")
+    assert dumps({"a": Int(-1), "b": Long(300)}, flavor="bedrock_network") == bytes.fromhex("0a0003016101040162d80400")
+    with pytest.raises(NBTParseError, match="VarInt is too long"):
+        loads(bytes.fromhex("0a0003016180808080808001"), flavor="bedrock_network")
@@ -0,0 +1,46 @@
+import math
+
+import pytest
+
+from nbtcompare import compare, compare_many, diff, dumps, from_snbt
+
+
+@pytest.mark.parametrize(
+    "left, right, options, expected",
+    [
+        ("{x:0.0d}", "{x:-0.0d}", {}, False),
+        ("{x:0.0d}", "{x:-0.0d}", {"float_mode": "ieee"}, True),
+        ("{x:[1.0f,2.0f]}", "{x:[1.0f,2.0001f]}", {"float_mode": "ieee"}, False),
+        ("{x:[1.0f,2.0f]}", "{x:[1.0f,2.0001f]}", {"float_mode": "tolerance", "abs_tol": 0.001}, True),
+        ("{x:[1.0d,2.0d]}", "{x:[1.0d,2.1d]}", {"float_mode": "tolerance", "rel_tol": 0.01}, False),
+        ("{x:[1.0d,2.0d]}", "{x:[1.0d,2.01d]}", {"float_mode": "tolerance", "rel_tol": 0.01}, True),
+        # only floats and doubles get a tolerance
+        ("{x:1}", "{x:2}", {"float_mode": "tolerance", "abs_tol": 5.0}, False),
+    ],
+)
+def test_float_mode(left, right, options, expected):
+    left, right = from_snbt(left), from_snbt(right)
+    assert compare(left, right, **options) == expected
+    assert (diff(left, right, **options) == []) == expected
+
+
+@pytest.mark.parametrize("float_mode, expected", [("bitwise", True), ("ieee", False), ("tolerance", False)])
+def test_nan(float_mode, expected):
+    nan = dumps({"x": math.nan})
+    assert compare(nan, nan, float_mode=float_mode) == expected
+    assert compare_many([(nan, nan)], float_mode=float_mode) == [expected]
+    assert (diff(nan, nan, float_mode=float_mode) == []) == expected
+    # the key order differs, so the buffers are not identical
+    left, right = dumps({"y": 1, "x": math.nan}), dumps({"x": math.nan, "y": 1})
+    assert compare(left, right, float_mode=float_mode) == expected
+    assert (diff(left, right, float_mode=float_mode) == []) == expected
+
+
+@pytest.mark.parametrize(
+    "options",
+    [{"abs_tol": 1.0}, {"float_mode": "tolerance", "rel_tol": -1.0}, {"float_mode": "approximate"}],
+)
+def test_invalid_options(options):
+    data = from_snbt("{x:1.0d}")
+    with pytest.raises(ValueError):
+        compare(data, data, **options)
@@ -0,0 +1,37 @@
+import pytest
+
+from nbtcompare import compare, diff, from_snbt
+
+
+@pytest.mark.parametrize(
+    "left, right, options, expected",
+    [
+        ("{Pos:[0.0d,1.0d,2.0d]}", "{Pos:[0.0d,1.0d,3.0d]}", {}, False),
+        ("{Pos:[0.0d,1.0d,2.0d]}", "{Pos:[0.0d,1.0d,3.0d]}", {"ignore": ["Pos[2]"]}, True),
+        ("{Pos:[0.0d,1.0d,2.0d]}", "{Pos:[0.0d,5.0d,3.0d]}", {"ignore": ["Pos[2]"]}, False),
+        ("{a:[1,2],b:1}", "{a:[1],b:1}", {"ignore": ["a[1]"]}, True),
+        ("{a:[1,2],b:1}", "{a:[1,2],b:2}", {"ignore": ["a[*]"]}, False),
+        ("{a:1,b:{c:2,UUID:3}}", "{b:{UUID:4,c:2},a:1}", {"ignore": ["**.UUID"]}, True),
+        ("{a:1,b:{c:2,UUID:3}}", "{b:{UUID:4,c:3},a:1}", {"ignore": ["**.UUID"]}, False),
+        ("{a:{x:1},b:{x:2}}", "{a:{x:3},b:{x:4}}", {"ignore": ["*.x"]}, True),
+        ("{a:1,LastUpdate:5L}", "{LastUpdate:6L,a:1}", {"exclude_last_update": True}, True),
+        ("{a:1,LastUpdate:5L}", "{LastUpdate:6L,a:1}", {}, False),
+    ],
+)
+def test_ignore(left, right, options, expected):
+    left, right = from_snbt(left), from_snbt(right)
+    assert compare(left, right, **options) == expected
+    assert (diff(left, right, **options) == []) == expected
+
+
+def test_ignored_paths_are_not_reported():
+    left = from_snbt("{Pos:[0.0d,1.0d,2.0d],Motion:[0.0d,0.0d,0.0d]}")
+    right = from_snbt("{Pos:[0.0d,1.0d,3.0d],Motion:[0.0d,0.5d,0.0d]}")
+    assert [difference.path for difference in diff(left, right)] == ["Motion", "Pos"]
+    assert [difference.path for difference in diff(left, right, ignore=["Pos[2]"])] == ["Motion"]
+
+
+@pytest.mark.parametrize("pattern", ["", "a..b", "a[", "a[x]"])
+def test_invalid_pattern(pattern):
+    with pytest.raises(ValueError):
+        compare(b"", b"", ignore=[pattern])
@@ -0,0 +1,39 @@
+import array
+import gzip
+
+from nbtcompare import from_snbt, loads
+from nbtcompare.tags import Byte, Float, Long, Short
+
+DATA = from_snbt('{z:1b,a:[1L,2L],f:0.5f,s:"x",b:[B;1B,2B],i:[I;1,2],l:[L;3L],e:[],c:{n:2s},t:true}')
+
+
+def test_native_types():
+    obj = loads(DATA)
+    assert list(obj) == sorted(obj)
+    assert obj == {
+        "a": [1, 2],
+        "b": b"\x01\x02",
+        "c": {"n": 2},
+        "e": [],
+        "f": 0.5,
+        "i": array.array("i", [1, 2]),
+        "l": array.array("q", [3]),
+        "s": "x",
+        "t": 1,
+        "z": 1,
+    }
+    assert type(obj["t"]) is int and type(obj["f"]) is float and type(obj["a"][0]) is int
+
+
+def test_preserve_types():
+    obj = loads(DATA, preserve_types=True)
+    assert type(obj["z"]) is Byte and type(obj["t"]) is Byte
+    assert type(obj["c"]["n"]) is Short
+    assert [type(value) for value in obj["a"]] == [Long, Long]
+    assert type(obj["f"]) is Float and obj["f"] == 0.5
+    # arrays and strings have no wrapper
+    assert type(obj["i"]) is array.array and type(obj["s"]) is str
+
+
+def test_compressed():
+    assert loads(gzip.compress(DATA)) == loads(DATA)
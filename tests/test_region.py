@@ -0,0 +1,114 @@
+import gzip
+import struct
+import zlib
+
+import pytest
+
+from nbtcompare import compare_region, from_snbt
+
+SECTOR = 4096
+
+
+# 4 is LZ4, which is not supported
+COMPRESS = {1: gzip.compress, 2: zlib.compress, 3: bytes, 4: bytes}
+
+
+def region(chunks, external=(), compression=2):
+    """Build a region file from ``{index: nbt}``, leaving the data of ``external`` chunks to .mcc files."""
+    header = bytearray(2 * SECTOR)
+    body = bytearray()
+    for index, data in sorted(chunks.items()):
+        payload = b"" if index in external else COMPRESS[compression](data)
+        compression_id = compression | 0x80 if index in external else compression
+        blob = struct.pack(">IB", len(payload) + 1, compression_id) + payload
+        blob += bytes(-len(blob) % SECTOR)
+        offset = 2 + len(body) // SECTOR
+        header[index * 4 : index * 4 + 4] = struct.pack(">I", offset << 8 | len(blob) // SECTOR)
+        body += blob
+    return bytes(header + body)
+
+
+def chunk(value):
+    return from_snbt(f"{{DataVersion:3465,Status:\"minecraft:full\",value:{value}}}")
+
+
+@pytest.mark.parametrize("compression", [1, 2, 3])
+def test_compare_region(compression):
+    left = region({0: chunk(1), 1: chunk(2), 2: chunk(3), 1023: chunk(4)}, compression=compression)
+    right = region({0: chunk(1), 2: chunk(5), 3: chunk(6), 1023: chunk(4)}, compression=compression)
+    result = compare_region(left, right)
+    assert (result.added, result.removed, result.changed, result.unchanged) == ([3], [1], [2], [0, 1023])
+    assert repr(result) == "<RegionComparison added=1 removed=1 changed=1 unchanged=2>"
+    assert compare_region(left, right, threads=None).changed == [2]
+
+
+def test_options_apply_to_chunks():
+    left = region({0: from_snbt("{DataVersion:3465,LastUpdate:1L}")})
+    right = region({0: from_snbt("{DataVersion:3465,LastUpdate:2L}")})
+    assert compare_region(left, right).changed == [0]
+    assert compare_region(left, right, exclude_last_update=True).unchanged == [0]
+    assert compare_region(left, right, ignore=["LastUpdate"]).unchanged == [0]
+
+
+def test_empty_region():
+    result = compare_region(b"", b"")
+    assert (result.added, result.removed, result.changed, result.unchanged) == ([], [], [], [])
+
+
+@pytest.mark.parametrize(
+    "data, message",
+    [
+        (bytes(100), "Region header is truncated"),
+        (region({0: chunk(1)})[: 2 * SECTOR + 10], "Chunk data is truncated"),
+        (region({0: chunk(1)}, compression=4), "Unsupported chunk compression: 4"),
+    ],
+)
+def test_malformed_region(data, message):
+    with pytest.raises(ValueError, match=message) as info:
+        compare_region(data, region({0: chunk(1)}))
+    assert info.value.__notes__[0].endswith("left")
+
+
+@pytest.fixture
+def external_chunks():
+    return {(1, 0): zlib.compress(chunk(1)), (2, 0): zlib.compress(chunk(2))}
+
+
+def test_directory_resolver(tmp_path, external_chunks):
+    for (x, z), data in external_chunks.items():
+        (tmp_path / f"c.{x}.{z}.mcc").write_bytes(data)
+    left = region({1: chunk(1), 2: chunk(3)})
+    right = region({1: chunk(1), 2: chunk(2)}, external={1, 2})
+    result = compare_region(left, right, right_external=tmp_path)
+    assert (result.unchanged, result.changed) == ([1], [2])
+    result = compare_region(left, right, right_external=str(tmp_path))
+    assert (result.unchanged, result.changed) == ([1], [2])
+
+
+def test_callable_resolver(external_chunks):
+    requested = []
+
+    def resolve(x, z):
+        requested.append((x, z))
+        return external_chunks[x, z]
+
+    left = region({1: chunk(1), 2: chunk(3)})
+    right = region({1: chunk(1), 2: chunk(2), 34: chunk(4)}, external={1, 2, 34})
+    result = compare_region(left, right, right_external=resolve, region_pos=(0, 0))
+    assert (result.added, result.unchanged, result.changed) == ([34], [1], [2])
+    # the added chunk is never compared, so its file is not requested
+    assert sorted(requested) == [(1, 0), (2, 0)]
+
+
+def test_added_and_removed_external_chunks():
+    right = region({5: chunk(1)}, external={5})
+    result = compare_region(b"", right)
+    assert (result.added, result.removed, result.changed) == ([5], [], [])
+    result = compare_region(right, b"")
+    assert (result.added, result.removed, result.changed) == ([], [5], [])
+
+
+def test_missing_resolver():
+    left = region({5: chunk(1)})
+    with pytest.raises(ValueError, match="no resolver"):
+        compare_region(left, region({5: chunk(1)}, external={5}))
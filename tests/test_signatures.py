@@ -0,0 +1,34 @@
+import inspect
+
+import pytest
+
+import nbtcompare
+
+SHARED = (
+    "exclude_last_update",
+    "ignore",
+    "ignore_data_version",
+    "unordered",
+    "chunk_aware",
+    "compression",
+    "flavor",
+    "nameless_root",
+    "strict_root",
+    "float_mode",
+    "abs_tol",
+    "rel_tol",
+)
+
+
+@pytest.mark.parametrize(
+    "func",
+    [nbtcompare.compare_many, nbtcompare.diff, nbtcompare.fingerprint, nbtcompare.compare_region],
+)
+def test_shared_options_match_compare(func):
+    expected = inspect.signature(nbtcompare.compare).parameters
+    params = inspect.signature(func).parameters
+    shared = [name for name in params if name in SHARED]
+    assert shared == [name for name in expected if name in shared]
+    for name in shared:
+        assert params[name].kind == expected[name].kind, name
+        assert params[name].default == expected[name].default, name
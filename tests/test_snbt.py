@@ -0,0 +1,86 @@
+import struct
+
+import pytest
+
+from nbtcompare import compare, from_snbt, loads, to_snbt
+from nbtcompare.tags import Byte, Double, Float, Int, Long, Short
+
+DATA = from_snbt('{z:1b,a:[1L,2L],f:0.5f,s:"x y",q:\'a"b\',b:[B;1B,2B],i:[I;1,2],l:[L;3L],e:[],c:{n:2s,m:[{}]}}')
+
+
+def test_compact():
+    assert to_snbt(DATA, False) == (
+        "{a:[1L,2L],b:[B;1b,2b],c:{m:[{}],n:2s},e:[],f:0.5f,i:[I;1,2],l:[L;3L],q:'a\"b',s:\"x y\",z:1b}"
+    )
+
+
+def test_pretty():
+    assert to_snbt(DATA) == "\n".join(
+        [
+            "{",
+            "    a: [1L, 2L],",
+            "    b: [B; 1b, 2b],",
+            "    c: {",
+            "        m: [",
+            "            {}",
+            "        ],",
+            "        n: 2s",
+            "    },",
+            "    e: [],",
+            "    f: 0.5f,",
+            "    i: [I; 1, 2],",
+            "    l: [L; 3L],",
+            "    q: 'a\"b',",
+            '    s: "x y",',
+            "    z: 1b",
+            "}",
+        ]
+    )
+
+
+@pytest.mark.parametrize("value, name", [(float("nan"), "NaN"), (float("inf"), "inf")])
+def test_non_finite_floats(value, name):
+    data = from_snbt("{a:1.0f}").replace(struct.pack(">f", 1.0), struct.pack(">f", value))
+    with pytest.raises(ValueError, match=f"{name} can not be represented in SNBT at a"):
+        to_snbt(data)
+
+
+def test_typed_values():
+    text = '{a:1b,b:2s,c:3L,d:4.0f,e:5d,f:6,g:7.5,h:true,i:false,j:hello,k:"1b"}'
+    obj = loads(from_snbt(text), preserve_types=True)
+    assert obj == {
+        "a": 1, "b": 2, "c": 3, "d": 4.0, "e": 5.0, "f": 6, "g": 7.5, "h": 1, "i": 0, "j": "hello", "k": "1b"
+    }
+    assert [type(obj[key]) for key in "abcdefghi"] == [Byte, Short, Long, Float, Double, Int, Double, Byte, Byte]
+
+
+def test_round_trip():
+    # keys come back sorted, so the buffers differ but the trees don't
+    assert compare(from_snbt(to_snbt(DATA)), DATA)
+    assert compare(from_snbt(to_snbt(DATA, False)), DATA)
+    assert to_snbt(from_snbt(to_snbt(DATA))) == to_snbt(DATA)
+
+
+def test_root_name():
+    assert from_snbt("{}", "r") == b"\x0a\x00\x01r\x00"
+    assert from_snbt("{}", None) == b"\x0a\x00"
+
+
+@pytest.mark.parametrize(
+    "text, message",
+    [
+        ("[" * 513 + "]" * 513, "Nested deeper than 512 levels"),
+        ("{a:1", "Expected ',' or '}'"),
+        ("{a:[1,2b]}", "Can't insert TAG_Byte into list of TAG_Int"),
+        ("[I;1b]", "Can't insert TAG_Byte into TAG_Int_Array"),
+        ("{a:1}x", "Trailing data"),
+    ],
+)
+def test_errors(text, message):
+    with pytest.raises(ValueError, match=message):
+        from_snbt(text)
+
+
+def test_nesting_limit():
+    assert from_snbt("[" * 512 + "]" * 512)
+    assert loads(from_snbt("{a:" + "[" * 511 + "]" * 511 + "}"))
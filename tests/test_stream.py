@@ -0,0 +1,57 @@
+import pytest
+
+from nbtcompare import NBTParseError, compare, compare_stats, diff, from_snbt
+
+
+@pytest.mark.parametrize(
+    "left, right, options, expected",
+    [
+        ("{a:1,b:[1,2]}", "{a:1,b:[1,2]}", {"float_mode": "ieee"}, True),
+        ("{a:1,b:[1,2]}", "{a:1,b:[1,3]}", {}, False),
+        ("{a:{x:1,y:2}}", "{a:{y:2,x:1}}", {}, True),
+        ("{a:{x:1,y:2}}", "{a:{y:2,x:2}}", {}, False),
+        ("{Pos:[0.0d,1.0d,2.0d]}", "{Pos:[0.0d,1.0d,3.0d]}", {"ignore": ["Pos[2]"]}, True),
+        ("{x:[1.0f,2.0f]}", "{x:[1.0f,2.0001f]}", {"float_mode": "tolerance", "abs_tol": 0.001}, True),
+        ("{x:[0.0d]}", "{x:[-0.0d]}", {"float_mode": "ieee"}, True),
+        ("{E:[{id:1,v:1},{id:2,v:2}]}", "{E:[{id:2,v:2},{id:1,v:1}]}", {"unordered": {"E": "id"}}, True),
+        (
+            "{Pos:[0.0d,0.0d,1.0d],E:[{id:1,v:1.0f},{id:2,v:2.0f}]}",
+            "{Pos:[0.0d,0.0d,2.0d],E:[{id:2,v:2.00001f},{id:1,v:1.0f}]}",
+            {"ignore": ["Pos[2]"], "unordered": {"E": "id"}, "float_mode": "tolerance", "rel_tol": 1e-4},
+            True,
+        ),
+    ],
+)
+def test_stream_and_tree_agree(left, right, options, expected):
+    left, right = from_snbt(left), from_snbt(right)
+    compare_stats(reset=True)
+    assert compare(left, right, **options) == expected
+    assert compare_stats()["streamed"] == 1
+    # diff always compares the parsed trees
+    assert (diff(left, right, **options) == []) == expected
+
+
+def test_map_fallback():
+    compare_stats(reset=True)
+    assert compare(from_snbt("{a:1,b:{x:1,y:2}}"), from_snbt("{a:1,b:{y:2,x:1}}"))
+    assert compare_stats() == {"identical": 0, "streamed": 1, "parsed": 0, "map_fallback": 1}
+
+
+@pytest.mark.parametrize(
+    "right",
+    [
+        # unknown tag id
+        b"\x0a\x00\x00\x01\x00\x01a\x01\x0e\x00\x01b\x00",
+        # truncated
+        b"\x0a\x00\x00\x01\x00\x01a\x01\x01\x00\x01b",
+        # list of TAG_End with elements
+        b"\x0a\x00\x00\x01\x00\x01a\x01\x09\x00\x01b\x00\x00\x00\x00\x02\x00",
+    ],
+)
+def test_malformed_after_difference(right):
+    # a:1b differs, so the walk stops before reaching the malformed entry
+    left = from_snbt("{a:2b,b:1b}")
+    with pytest.raises(NBTParseError):
+        compare(left, right)
+    with pytest.raises(NBTParseError):
+        diff(left, right)
@@ -0,0 +1,39 @@
+import pytest
+
+from nbtcompare import compare, diff, fingerprint, from_snbt
+
+
+@pytest.mark.parametrize(
+    "left, right, options, expected",
+    [
+        ("{E:[1,2,3]}", "{E:[3,1,2]}", {}, False),
+        ("{E:[1,2,3]}", "{E:[3,1,2]}", {"unordered": ["E"]}, True),
+        ("{E:[1,1,2]}", "{E:[1,2,2]}", {"unordered": ["E"]}, False),
+        ("{a:{E:[1,2]}}", "{a:{E:[2,1]}}", {"unordered": ["**.E"]}, True),
+        ("{E:[{id:1,v:1},{id:2,v:2}]}", "{E:[{id:2,v:2},{id:1,v:1}]}", {"unordered": {"E": "id"}}, True),
+        ("{E:[{id:1,v:1},{id:2,v:2}]}", "{E:[{id:2,v:1},{id:1,v:2}]}", {"unordered": {"E": "id"}}, False),
+        # greedy pairing would match 1.0 with 1.4 and leave 1.5 without a partner
+        ("{E:[1.0d,1.5d]}", "{E:[1.4d,0.6d]}", {"unordered": ["E"], "float_mode": "tolerance", "abs_tol": 0.5}, True),
+    ],
+)
+def test_unordered(left, right, options, expected):
+    left, right = from_snbt(left), from_snbt(right)
+    assert compare(left, right, **options) == expected
+    assert (diff(left, right, **options) == []) == expected
+
+
+def test_keyed_changes():
+    left = from_snbt("{E:[{id:1,v:1},{id:2,v:2}]}")
+    right = from_snbt("{E:[{id:2,v:3},{id:1,v:1},{id:3,v:4}]}")
+    differences = diff(left, right, unordered={"E": "id"})
+    assert [(difference.kind, difference.path) for difference in differences] == [
+        ("changed", "E[1].v"),
+        ("added", "E[2]"),
+    ]
+
+
+def test_fingerprint():
+    options = {"unordered": {"E": "id"}}
+    left = from_snbt("{E:[{id:1,v:1},{id:2,v:2}]}")
+    assert fingerprint(left, **options) == fingerprint(from_snbt("{E:[{id:2,v:2},{id:1,v:1}]}"), **options)
+    assert fingerprint(left) != fingerprint(from_snbt("{E:[{id:2,v:2},{id:1,v:1}]}"))